@@ -1,10 +1,11 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use schnauzer_ui::{
     datatable::{preprocess, read_csv},
     interpreter::Interpreter,
     parser::Stmt,
     scanner::Scanner,
+    settings::{parse_seconds, Settings},
     webdriver::{new_driver, SupportedBrowser, WebDriverConfig},
 };
 
@@ -45,6 +46,40 @@ struct Cli {
     /// The port your webdriver compliant process is running on
     #[arg(long, short, default_value_t = 4444)]
     port: usize,
+
+    /// Directory the browser saves downloads to.
+    /// Defaults to a "browser_downloads" directory inside the output directory.
+    #[arg(long)]
+    download_directory: Option<Utf8PathBuf>,
+
+    /// Comma separated locator strategies for the locate command to try, in order.
+    /// Ex. "id,text,placeholder"
+    #[arg(long)]
+    locator_order: Option<String>,
+
+    /// How many seconds the locate command keeps looking for an element before failing.
+    /// Scripts can change it with `set locate-timeout`.
+    #[arg(long)]
+    locate_timeout: Option<String>,
+
+    /// How many milliseconds the locate command waits between looks for an element.
+    #[arg(long)]
+    poll_interval: Option<u64>,
+
+    /// Record failures in the report and keep running the script, instead of stopping
+    /// (or skipping ahead to a catch-error).
+    #[arg(long)]
+    keep_going: bool,
+
+    /// How many seconds a statement can run before it fails with a timeout.
+    /// Scripts can change it with `timeout "30"`.
+    #[arg(long)]
+    statement_timeout: Option<String>,
+
+    /// How many seconds the whole script can run before it's stopped with a timeout.
+    /// The cleanup section still runs afterwards. Doesn't apply to the REPL.
+    #[arg(long)]
+    script_timeout: Option<String>,
 }
 
 fn main() {
@@ -75,6 +110,13 @@ async fn start(
         browser,
         demo,
         port,
+        download_directory,
+        locator_order,
+        locate_timeout,
+        poll_interval,
+        keep_going,
+        statement_timeout,
+        script_timeout,
     }: Cli,
 ) -> Result<()> {
     // Create the provided output directory.
@@ -87,8 +129,29 @@ async fn start(
         port,
         headless,
         browser,
+        download_directory: Some(
+            download_directory.unwrap_or_else(|| output_directory.join("browser_downloads")),
+        ),
     };
 
+    // Settings which apply to every script, unless a script changes them with `set`
+    let mut settings = Settings::default();
+    if let Some(order) = locator_order {
+        settings.set("locator-order", &order)?;
+    }
+    if let Some(timeout) = locate_timeout {
+        settings.set("locate-timeout", &timeout)?;
+    }
+    if let Some(interval) = poll_interval {
+        settings.set("poll-interval", &interval.to_string())?;
+    }
+    if let Some(timeout) = statement_timeout {
+        settings.set("statement-timeout", &timeout)?;
+    }
+    let script_timeout = script_timeout
+        .map(|timeout| parse_seconds(&timeout))
+        .transpose()?;
+
     // Delegate based on provided cli arguments
     match input_filepath {
         // They provided a filepath, so verify it's a file and just run the given file
@@ -106,6 +169,9 @@ async fn start(
                 output_directory,
                 driver_config,
                 demo,
+                settings,
+                keep_going,
+                script_timeout,
             }
             .run()
             .await?;
@@ -113,7 +179,7 @@ async fn start(
 
         // They did not provide a filepath, so run in REPL mode
         None => {
-            ReplRunner::new(output_directory, driver_config, demo)
+            ReplRunner::new(output_directory, driver_config, demo, settings)
                 .await?
                 .run()
                 .await?;
@@ -129,13 +195,38 @@ struct FileRunner {
     output_directory: Utf8PathBuf,
     driver_config: WebDriverConfig,
     demo: bool,
+    settings: Settings,
+    keep_going: bool,
+    script_timeout: Option<Duration>,
 }
 
 impl FileRunner {
     pub async fn run(self) -> Result<()> {
         let tokens = Scanner::from_src(self.process_input_file()?).scan();
         let stmts = schnauzer_ui::parser::Parser::new().parse(tokens)?;
-        let interpreter = Interpreter::new(new_driver(self.driver_config).await?, stmts, self.demo);
+        let mut interpreter = Interpreter::new(
+            new_driver(self.driver_config.clone()).await?,
+            stmts,
+            self.demo,
+        );
+        if let Some(ref dir) = self.driver_config.download_directory {
+            interpreter.set_download_directory(dir.clone());
+        }
+        if let Some(dir) = self.input_filepath.parent() {
+            interpreter.set_script_directory(dir.to_owned());
+        }
+        interpreter.settings = self.settings.clone();
+        interpreter.set_keep_going(self.keep_going);
+        if let Some(timeout) = self.script_timeout {
+            interpreter.set_script_timeout(timeout);
+        }
+
+        // Fingerprints of located elements live next to the report, to heal locators on later runs
+        interpreter.set_locator_cache(
+            &self
+                .output_directory
+                .join(format!("{}_locators.json", self.get_filename_for_report()?)),
+        )?;
         let mut report = interpreter.interpret(true).await?;
         report
             .set_testname(self.get_filename_for_report()?)
@@ -181,15 +272,21 @@ impl ReplRunner {
         output_filepath: Utf8PathBuf,
         driver_config: WebDriverConfig,
         is_demo: bool,
+        settings: Settings,
     ) -> Result<Self> {
-        let driver = new_driver(driver_config).await?;
+        let driver = new_driver(driver_config.clone()).await?;
+        let mut interpreter = Interpreter::new(driver, vec![], is_demo);
+        if let Some(dir) = driver_config.download_directory {
+            interpreter.set_download_directory(dir);
+        }
+        interpreter.settings = settings;
         Ok(Self {
             // Passed in
             output_filepath,
 
             // Initializers
             script_buffer: String::new(),
-            interpreter: Interpreter::new(driver, vec![], is_demo),
+            interpreter,
         })
     }
 
@@ -224,10 +321,15 @@ impl ReplRunner {
                 break;
             }
             let tokens = Scanner::from_src(code).scan();
-            match schnauzer_ui::parser::Parser::new().parse(tokens) {
+
+            // Blocks like `within` are opened and closed on separate lines
+            match schnauzer_ui::parser::Parser::new()
+                .allow_open_blocks()
+                .parse(tokens)
+            {
                 Ok(stmts) => {
                     for stmt in stmts.iter() {
-                        if let Err(e) = self.interpreter.execute_stmt(stmt.clone()).await {
+                        if let Err(e) = self.interpreter.execute_stmt_in_time(stmt.clone()).await {
                             eprintln!("The statement {} resulted in an error: {}", stmt, e);
                         }
                         if Self::prompt_save_statement()? {
@@ -280,7 +382,7 @@ impl ReplRunner {
     }
 
     async fn execute_startup_statement(&mut self, stmt: Stmt) -> Result<()> {
-        if let Err(e) = self.interpreter.execute_stmt(stmt).await {
+        if let Err(e) = self.interpreter.execute_stmt_in_time(stmt).await {
             bail!(
                 "Warning: Error encountered while running start script: {}",
                 e
@@ -0,0 +1,109 @@
+//! This module contains code for finding files the browser has downloaded.
+
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Suffixes browsers give files while a download is still in progress.
+const PARTIAL_DOWNLOAD_SUFFIXES: [&str; 3] = [".part", ".crdownload", ".tmp"];
+
+/// Matches a file name against a pattern where `*` matches any run of characters
+/// and `?` matches a single character, like `*.csv` or `report-??.pdf`.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // Position to resume from if a `*` needs to swallow another character
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    // Any leftover pattern has to be stars
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The files already in `dir` and when each was last modified, so files left there by an
+/// earlier run aren't mistaken for new downloads. Empty if the directory can't be read.
+pub fn existing_files(dir: &Utf8Path) -> Vec<(Utf8PathBuf, SystemTime)> {
+    let Ok(entries) = dir.read_dir_utf8() else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path().to_owned(), modified))
+        })
+        .collect()
+}
+
+/// Finds the most recently modified, fully downloaded file in `dir` matching `pattern`,
+/// skipping any file in `ignore` (downloads a previous command already claimed) and any
+/// file in `existing` which hasn't been modified since (see `existing_files`).
+pub fn find_completed_download(
+    dir: &Utf8Path,
+    pattern: &str,
+    ignore: &[Utf8PathBuf],
+    existing: &[(Utf8PathBuf, SystemTime)],
+) -> Result<Option<Utf8PathBuf>> {
+    let mut newest = None;
+    for entry in dir
+        .read_dir_utf8()
+        .with_context(|| format!("Could not read download directory: {}", dir))?
+    {
+        let entry = entry?;
+        let path = entry.path().to_owned();
+        let name = entry.file_name();
+
+        let is_partial = PARTIAL_DOWNLOAD_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix));
+
+        // Firefox creates the final file right away and fills in a `.part` file beside it
+        let has_partial_sibling = PARTIAL_DOWNLOAD_SUFFIXES
+            .iter()
+            .any(|suffix| dir.join(format!("{}{}", name, suffix)).exists());
+
+        if is_partial
+            || has_partial_sibling
+            || !path.is_file()
+            || ignore.contains(&path)
+            || !matches_pattern(pattern, name)
+        {
+            continue;
+        }
+
+        let modified = entry.metadata()?.modified()?;
+        if existing.contains(&(path.clone(), modified)) {
+            continue;
+        }
+        let is_newest = match newest {
+            Some((time, _)) => modified > time,
+            None => true,
+        };
+        if is_newest {
+            newest = Some((modified, path));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
@@ -1,12 +1,46 @@
 //! The "Environment" is where the interpreter keeps track of variable values.
 //! As you can see, it's nothing fancy.
 
+use serde::Deserialize;
 use std::collections::HashMap;
 
+/// The value of a variable. Most variables are text, but some
+/// commands read structured data from the page.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Plain text, like from `save` or `read-to`
+    Text(String),
+
+    /// The cells of an html table, from `read-table-to`
+    Table(Table),
+}
+
+/// Text read from an html table (or ARIA grid), with each row's cells in the same order as the headers.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Table {
+    /// The text of the column headers
+    pub headers: Vec<String>,
+
+    /// The text of the cells in each row, not including the header row
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Get the cells of a row, counting from 1 like the rows of the table appear on the page.
+    pub fn row(&self, number: usize) -> Option<&Vec<String>> {
+        number.checked_sub(1).and_then(|i| self.rows.get(i))
+    }
+
+    /// Get the position of the column with the given header text.
+    pub fn column(&self, header: &str) -> Option<usize> {
+        self.headers.iter().position(|h| h == header)
+    }
+}
+
 /// Represents the "state" of the programs execution. Basically
 /// keeps track of variables and their values.
 #[derive(Debug)]
-pub struct Environment(HashMap<String, String>);
+pub struct Environment(HashMap<String, Value>);
 
 impl Environment {
     /// Creates a new environment
@@ -17,13 +51,18 @@ impl Environment {
     /// Set a variable value. SchnauzerUI makes no distinction between
     /// declaration and instantiation.
     pub fn set_variable(&mut self, name: String, value: String) {
+        self.set_value(name, Value::Text(value));
+    }
+
+    /// Set a variable to a value which may be structured.
+    pub fn set_value(&mut self, name: String, value: Value) {
         let _ = self.0.insert(name, value);
     }
 
     /// Get the value of a variable if it exists, or None
     /// if it does not.
-    pub fn get_variable(&self, name: &str) -> Option<String> {
-        self.0.get(name).cloned()
+    pub fn get_value(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
     }
 }
 
@@ -0,0 +1,145 @@
+//! The kinds of failure a script can run into. A `catch-error` statement can name the
+//! kinds it handles, and skips over failures of any other kind.
+//!
+//! ```sui
+//! catch-error "not-found" "timeout": refresh and try-again
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::bail;
+use fantoccini::error::CmdError;
+use thirtyfour::prelude::WebDriverError;
+
+/// A kind of failure, named in `catch-error` statements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// No element matched a locator (`"not-found"`).
+    NotFound,
+
+    /// The browser took too long to do something (`"timeout"`).
+    Timeout,
+
+    /// The element was found, but couldn't be clicked or typed into, like when it's
+    /// covered by another element or disabled (`"not-interactable"`).
+    NotInteractable,
+
+    /// An alert is open, blocking the page (`"alert-open"`).
+    AlertOpen,
+
+    /// Something the script expected didn't happen, like a download (`"assertion-failed"`).
+    AssertionFailed,
+
+    /// A locator matched several elements, with `set ambiguous-locators "fail"` (`"ambiguous"`).
+    Ambiguous,
+
+    /// The browser couldn't load a url (`"navigation"`).
+    Navigation,
+
+    /// JavaScript on the page threw an error (`"script"`).
+    Script,
+}
+
+impl ErrorKind {
+    /// Every kind, in the order they're listed in error messages.
+    pub const ALL: [ErrorKind; 8] = [
+        ErrorKind::NotFound,
+        ErrorKind::Timeout,
+        ErrorKind::NotInteractable,
+        ErrorKind::AlertOpen,
+        ErrorKind::AssertionFailed,
+        ErrorKind::Ambiguous,
+        ErrorKind::Navigation,
+        ErrorKind::Script,
+    ];
+
+    /// The kind of a failure, if it has one. Failures raised by the interpreter carry their kind
+    /// (see `ScriptError`), and failures from the browser are sorted by their WebDriver error code.
+    pub fn of(error: &anyhow::Error) -> Option<ErrorKind> {
+        // Downcasting looks through any context added to the error
+        if let Some(e) = error.downcast_ref::<ScriptError>() {
+            return Some(e.kind);
+        }
+        match error.downcast_ref::<WebDriverError>()? {
+            WebDriverError::NoSuchElement(_) => Some(ErrorKind::NotFound),
+            WebDriverError::Timeout(_) => Some(ErrorKind::Timeout),
+            WebDriverError::CmdError(CmdError::WaitTimeout) => Some(ErrorKind::Timeout),
+            WebDriverError::CmdError(CmdError::Standard(e)) => match e.error() {
+                "timeout" | "script timeout" => Some(ErrorKind::Timeout),
+                "element not interactable"
+                | "element click intercepted"
+                | "invalid element state" => Some(ErrorKind::NotInteractable),
+                "unexpected alert open" => Some(ErrorKind::AlertOpen),
+                "javascript error" => Some(ErrorKind::Script),
+                "insecure certificate" => Some(ErrorKind::Navigation),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::NotInteractable => "not-interactable",
+            ErrorKind::AlertOpen => "alert-open",
+            ErrorKind::AssertionFailed => "assertion-failed",
+            ErrorKind::Ambiguous => "ambiguous",
+            ErrorKind::Navigation => "navigation",
+            ErrorKind::Script => "script",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ErrorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match ErrorKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+        {
+            Some(kind) => Ok(kind),
+            None => bail!(
+                "Unknown kind of error \"{}\". Expected one of {}",
+                s,
+                ErrorKind::ALL
+                    .iter()
+                    .map(|kind| format!("\"{}\"", kind))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// A failure raised by the interpreter, tagged with its kind.
+#[derive(Debug)]
+pub struct ScriptError {
+    /// What kind of failure it is
+    pub kind: ErrorKind,
+
+    /// What went wrong, for the report
+    pub message: String,
+}
+
+impl ScriptError {
+    pub fn new(kind: ErrorKind, message: impl Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
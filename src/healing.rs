@@ -0,0 +1,190 @@
+//! Self-healing locators. After `locate` finds an element, the interpreter records a
+//! fingerprint of it (its tag, id, classes, text, attributes and place in the page) in a cache
+//! file which lives next to the test report. On a later run, if the same locator in the same
+//! statement stops matching (say the button's text changed from "Sign in" to "Log in"),
+//! the element on the page most like the fingerprint is used instead, and the report
+//! flags the statement as healed with a suggested new locator.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::locator::edit_distance;
+
+/// How alike (from 0 to 1) an element has to be to a fingerprint to stand in for it.
+const HEALING_THRESHOLD: f64 = 0.6;
+
+/// Text longer than this isn't suggested as a locator.
+const MAX_SUGGESTED_TEXT_LEN: usize = 50;
+
+/// What an element looked like when it was located.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The tag name, like `"button"`
+    pub tag: String,
+
+    /// The id attribute, or an empty string
+    pub id: String,
+
+    /// The classes in the class attribute
+    pub classes: Vec<String>,
+
+    /// The element's text, with whitespace collapsed
+    pub text: String,
+
+    /// The tags from the top of the document down to the element, with their position among
+    /// siblings of the same tag, like `"html/body/form[1]/button[2]"`
+    pub path: String,
+
+    /// Every other attribute, except style
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Fingerprint {
+    /// How alike two fingerprints are, from 0 (nothing in common) to 1 (identical).
+    /// Elements with different tags are never alike.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        if self.tag != other.tag {
+            return 0.0;
+        }
+
+        // Each feature either fingerprint has counts towards the score, by its weight
+        let mut score = 1.0;
+        let mut total = 1.0;
+        let mut weigh = |weight: f64, present: bool, similarity: f64| {
+            if present {
+                score += weight * similarity;
+                total += weight;
+            }
+        };
+
+        weigh(
+            3.0,
+            !self.id.is_empty() || !other.id.is_empty(),
+            f64::from(u8::from(self.id == other.id)),
+        );
+        weigh(
+            3.0,
+            !self.text.is_empty() || !other.text.is_empty(),
+            text_similarity(&self.text, &other.text),
+        );
+        weigh(
+            1.5,
+            !self.classes.is_empty() || !other.classes.is_empty(),
+            jaccard(self.classes.iter(), other.classes.iter()),
+        );
+        weigh(
+            2.0,
+            !self.attributes.is_empty() || !other.attributes.is_empty(),
+            jaccard(self.attributes.iter(), other.attributes.iter()),
+        );
+        weigh(2.0, true, path_similarity(&self.path, &other.path));
+
+        score / total
+    }
+
+    /// A locator likely to find this element: its text if it's short, otherwise
+    /// its id, placeholder or aria-label.
+    pub fn suggested_locator(&self) -> Option<String> {
+        if !self.text.is_empty() && self.text.chars().count() <= MAX_SUGGESTED_TEXT_LEN {
+            return Some(self.text.clone());
+        }
+        if !self.id.is_empty() {
+            return Some(format!("id:{}", self.id));
+        }
+        ["placeholder", "aria-label"]
+            .iter()
+            .find_map(|attr| self.attributes.get(*attr).cloned())
+    }
+}
+
+/// Pick the candidate most like the fingerprint, if any is alike enough to stand in for it.
+/// Returns its position in `candidates`.
+pub fn best_match(fingerprint: &Fingerprint, candidates: &[Fingerprint]) -> Option<usize> {
+    candidates
+        .iter()
+        .map(|candidate| fingerprint.similarity(candidate))
+        .enumerate()
+        .filter(|(_, similarity)| *similarity >= HEALING_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
+
+/// 1 minus the edit distance between two texts relative to the longer one.
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(&a, &b) as f64 / longest as f64
+}
+
+/// The share of the items in either set which are in both.
+fn jaccard<T: PartialEq>(a: impl Iterator<Item = T>, b: impl Iterator<Item = T>) -> f64 {
+    let a = a.collect::<Vec<_>>();
+    let b = b.collect::<Vec<_>>();
+    let both = a.iter().filter(|item| b.contains(item)).count();
+    let either = a.len() + b.len() - both;
+    if either == 0 {
+        return 1.0;
+    }
+    both as f64 / either as f64
+}
+
+/// The share of the path segments, from the top of the document, which are the same.
+fn path_similarity(a: &str, b: &str) -> f64 {
+    let a = a.split('/').collect::<Vec<_>>();
+    let b = b.split('/').collect::<Vec<_>>();
+    let common = a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count();
+    common as f64 / a.len().max(b.len()) as f64
+}
+
+/// The fingerprints of located elements, keyed by the statement and locator which found them.
+/// Stored as json.
+#[derive(Debug)]
+pub struct LocatorCache {
+    /// Where the cache is saved
+    path: Utf8PathBuf,
+
+    /// The fingerprints, by key
+    fingerprints: HashMap<String, Fingerprint>,
+}
+
+impl LocatorCache {
+    /// Load the cache from a file, or start an empty one if the file doesn't exist yet.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let fingerprints = if path.exists() {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("Error reading locator cache {}", path))?;
+            serde_json::from_str(&json)
+                .with_context(|| format!("Error parsing locator cache {}", path))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path: path.to_owned(),
+            fingerprints,
+        })
+    }
+
+    /// Write the cache back to its file.
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.fingerprints)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("Error writing locator cache {}", self.path))
+    }
+
+    /// Get the fingerprint recorded for a key.
+    pub fn get(&self, key: &str) -> Option<&Fingerprint> {
+        self.fingerprints.get(key)
+    }
+
+    /// Record the fingerprint of the element a key found.
+    pub fn insert(&mut self, key: String, fingerprint: Fingerprint) {
+        let _ = self.fingerprints.insert(key, fingerprint);
+    }
+}
@@ -1,16 +1,30 @@
 //! The interpreter is responsible for executing Schnauzer UI stmts. It translates Schnauzer UI
 //! statements into thirtyfour queries.
 
+use std::time::SystemTime;
+
 use anyhow::{bail, Context, Result};
 use async_recursion::async_recursion;
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use thirtyfour::{components::SelectElement, prelude::*};
+use tokio::time::{Duration, Instant};
 
 use crate::{
-    environment::Environment,
-    js::SIMULATE_DRAG_AND_DROP,
-    parser::{Cmd, CmdParam, CmdStmt, IfStmt, SetVariableStmt, Stmt},
-    test_report::{ExecutedStmt, StandardReport},
+    downloads::{existing_files, find_completed_download},
+    environment::{Environment, Table, Value},
+    error::{ErrorKind, ScriptError},
+    healing::{best_match, Fingerprint, LocatorCache},
+    js::{
+        COUNT_DISPLAYED, DOM_FUNCTIONS, ELEMENT_RECTS, FIND_LABELLED_CONTROL, FIND_TABLE_ROW,
+        FINGERPRINT, FINGERPRINT_CANDIDATES, FINGERPRINT_FUNCTIONS, LOCATOR_CANDIDATES,
+        PARENT_ELEMENT, READ_TABLE, ROLE_FUNCTIONS, SIMULATE_DRAG_AND_DROP, TABLE_FUNCTIONS,
+    },
+    locator::{closest_matches, rank_by_relation, Candidate, LocatorMatch, Rect, SearchRoot},
+    parser::{
+        Cmd, CmdParam, CmdStmt, IfStmt, Index, Locator, Ordinal, SetVariableStmt, SettingStmt, Stmt,
+    },
+    settings::{format_seconds, parse_seconds, AmbiguityMode, DragMode, Settings, UnderMode},
+    test_report::{ExecutedStmt, FailureArtifacts, HealedLocator, StandardReport},
 };
 
 /// The interpreter is responsible for executing Schnauzer UI stmts. It translates Schnauzer UI
@@ -22,6 +36,10 @@ pub struct Interpreter {
     /// The statements for the interpreter to execute
     stmts: Vec<Stmt>,
 
+    /// The statements of the cleanup section, which run after the rest of the script
+    /// no matter how it ended.
+    cleanup_stmts: Vec<Stmt>,
+
     /// Each interpreter gets an environment for storing variables
     environment: Environment,
 
@@ -31,95 +49,441 @@ pub struct Interpreter {
 
     /// The last locator used to locate an element. Stored
     /// to re-execute locate command when necessary (like for a stale element)
-    last_used_locator: Option<String>,
+    last_used_locator: Option<Locator>,
 
     /// The had error field tracks whether or not the script encountered an error, and is used to move between catch-error: statements.
     had_error: bool,
 
+    /// The kind of the error the script encountered, if it has one. Catch-error statements
+    /// which name kinds only handle errors of those kinds.
+    error_kind: Option<ErrorKind>,
+
     /// We store the statements that we encounter since the last catch-error stmt in order for
     /// the try-again command to be able to re-execute them.
     statements_since_last_error_handling: Vec<Stmt>,
 
+    /// How many `within` blocks were open when the statements since the last catch-error stmt
+    /// started being collected. try-again closes any opened since, as re-executing opens them again.
+    scope_depth_at_last_error_handling: usize,
+
+    /// Which retry of the statements since the last catch-error stmt is running, counting from 1,
+    /// or 0 for the first attempt. Caps how many times try-again re-executes them.
+    retry: usize,
+
     /// The progress of the program is stored into an object to optionally be written to a file
     pub report: StandardReport,
 
     /// A buffer for storing png bytes of screenshots taken during testing
     screenshot_buffer: Vec<Vec<u8>>,
 
+    /// A buffer for storing the warnings raised while executing the current statement
+    warning_buffer: Vec<String>,
+
+    /// A buffer for storing notes about what the current statement did, like swapping a label for its input
+    note_buffer: Vec<String>,
+
+    /// A buffer for storing the locators healed while executing the current statement
+    healed_buffer: Vec<HealedLocator>,
+
+    /// The text of the statement being executed, for keying the locator cache
+    current_stmt: String,
+
+    /// Fingerprints of located elements from earlier runs, for healing locators
+    /// which stop matching. Only kept when a cache file is set.
+    locator_cache: Option<LocatorCache>,
+
     /// Denotes whether the program is in "demo" mode
     is_demo: bool,
 
+    /// Whether every failure is soft: recorded in the report without stopping the script
+    /// (see `Stmt::Soft`).
+    keep_going: bool,
+
+    /// How long the whole script can run, not counting its cleanup section, or None for no limit.
+    script_timeout: Option<Duration>,
+
+    /// When the running script's (or its cleanup section's) time is up, if it has a timeout,
+    /// and the error message for running past it.
+    deadline: Option<(Instant, String)>,
+
+    /// The statement timeout the browser's page load and script timeouts were last set from,
+    /// where None means the browser's own defaults.
+    browser_timeout: Option<Duration>,
+
     /// Base for when the under command is used
     under_element: Option<WebElement>,
+
+    /// The `within` blocks the script is in, innermost last. Searches start inside the innermost one.
+    scopes: Vec<Scope>,
+
+    /// Settings which change how commands behave, adjustable from a script with `set`
+    pub settings: Settings,
+
+    /// The directory the browser saves downloads to. Must match the directory
+    /// the driver was configured with (see `WebDriverConfig`).
+    download_directory: Option<Utf8PathBuf>,
+
+    /// Every download claimed by `expect-download` so far, so the same file isn't matched twice.
+    /// The last one is what `read-download-to` reads.
+    downloads: Vec<Utf8PathBuf>,
+
+    /// The files in the download directory when it was set, and when each was last modified.
+    /// They were left by something else, like an earlier run, so `expect-download` skips them.
+    existing_downloads: Vec<(Utf8PathBuf, SystemTime)>,
+
+    /// A buffer for storing the downloads claimed while executing the current statement
+    download_buffer: Vec<Utf8PathBuf>,
+
+    /// The directory of the script being run. Relative paths in the script
+    /// (like files to upload) are resolved against it.
+    script_directory: Option<Utf8PathBuf>,
 }
 
+/// A `within` block the script is in.
+#[derive(Clone)]
+struct Scope {
+    /// How the block located its element, for locating it again if it goes stale
+    locator: Locator,
+
+    /// The element searches are limited to, or None if it couldn't be located
+    element: Option<WebElement>,
+}
+
+/// The number of pointer moves a drag is broken into. Libraries driven by mouse events
+/// often ignore a single jump from the start to the end position.
+const DRAG_STEPS: i64 = 10;
+
+/// How many times `try-again` re-executes the statements since the last catch-error
+/// stmt when the script doesn't say.
+const DEFAULT_RETRIES: usize = 3;
+
+/// How long capturing what the page looked like after a failure can take. The page may be
+/// what's stuck.
+const ARTIFACTS_TIMEOUT_SECS: u64 = 10;
+
+/// How long closing the browser window at the end of a script can take.
+const CLOSE_WINDOW_TIMEOUT_SECS: u64 = 10;
+
+/// The WebDriver defaults for how long the browser lets a page load and a script run,
+/// restored when the statement timeout is lifted.
+const DEFAULT_PAGE_LOAD_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_SCRIPT_TIMEOUT_SECS: u64 = 30;
+
+/// How long `expect-download` waits for a download to finish.
+const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+/// How often `expect-download` checks the download directory.
+const DOWNLOAD_POLL_MILLIS: u64 = 500;
+
 impl Interpreter {
     /// Constructor for the Interpreter. Registers a webdriver against a standalone selenium grid running at port 4444.
     pub fn new(driver: WebDriver, stmts: Vec<Stmt>, is_demo: bool) -> Self {
+        // The statements after `cleanup:` are kept apart, to run once the script is done
+        let mut stmts = stmts;
+        let cleanup_stmts = match stmts.iter().position(|stmt| *stmt == Stmt::Cleanup) {
+            Some(i) => stmts.split_off(i).into_iter().skip(1).rev().collect(),
+            None => vec![],
+        };
         let stmts = stmts.into_iter().rev().collect();
 
         Self {
             // Provided fields
             driver,
             stmts,
+            cleanup_stmts,
             is_demo,
 
             // Initializers
+            keep_going: false,
+            script_timeout: None,
+            deadline: None,
+            browser_timeout: None,
             report: StandardReport::new(),
             environment: Environment::new(),
             current_element: None,
             had_error: false,
+            error_kind: None,
             statements_since_last_error_handling: vec![],
+            scope_depth_at_last_error_handling: 0,
+            retry: 0,
             screenshot_buffer: vec![],
+            warning_buffer: vec![],
+            note_buffer: vec![],
+            healed_buffer: vec![],
+            current_stmt: String::new(),
+            locator_cache: None,
             last_used_locator: None,
             under_element: None,
+            scopes: vec![],
+            settings: Settings::default(),
+            download_directory: None,
+            downloads: vec![],
+            existing_downloads: vec![],
+            download_buffer: vec![],
+            script_directory: None,
         }
     }
 
+    /// Set the directory of the script being run, so relative paths in it resolve
+    /// the same no matter where it's run from.
+    pub fn set_script_directory(&mut self, script_directory: Utf8PathBuf) -> &mut Self {
+        self.script_directory = Some(script_directory);
+        self
+    }
+
+    /// Set the directory the browser saves downloads to, for the `expect-download` command.
+    /// Files already in it are left out of what `expect-download` can match.
+    pub fn set_download_directory(&mut self, download_directory: Utf8PathBuf) -> &mut Self {
+        self.existing_downloads = existing_files(&download_directory);
+        self.download_directory = Some(download_directory);
+        self
+    }
+
+    /// Record failures in the report and keep running the script, rather than stopping
+    /// (or skipping ahead to a catch-error stmt).
+    pub fn set_keep_going(&mut self, keep_going: bool) -> &mut Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Fail the script with a timeout if it runs longer than the given time. The cleanup
+    /// section still runs afterwards, and gets the same amount of time again.
+    pub fn set_script_timeout(&mut self, script_timeout: Duration) -> &mut Self {
+        self.script_timeout = Some(script_timeout);
+        self
+    }
+
+    /// Keep fingerprints of located elements in the given file, and use them to heal
+    /// locators which stop matching on later runs (see the `healing` module).
+    pub fn set_locator_cache(&mut self, path: &Utf8Path) -> Result<&mut Self> {
+        self.locator_cache = Some(LocatorCache::load(path)?);
+        Ok(self)
+    }
+
     /// "Reset" the interpreter to reuse it.
     fn reset(&mut self) {
         self.current_element = None;
         self.had_error = false;
+        self.error_kind = None;
         self.statements_since_last_error_handling.clear();
+        self.retry = 0;
+        self.scopes.clear();
     }
 
     /// Executes a list of stmts. Returns a boolean indication of whether or not there was an early return.
     pub async fn interpret(mut self, close_driver: bool) -> Result<StandardReport> {
         self.reset();
 
+        self.deadline = self.script_timeout.map(|timeout| {
+            let message = format!("The script took longer than {}", format_seconds(timeout));
+            (Instant::now() + timeout, message)
+        });
+        self.run_stmts(false).await;
+
+        // If had_error is still true here, it means we had to do an early exit
+        let exited_early = self.had_error;
+
+        // The cleanup section runs no matter how the script ended. Its failures are recorded,
+        // but don't stop it or change whether the script exited early.
+        if !self.cleanup_stmts.is_empty() {
+            self.reset();
+            self.under_element = None;
+            self.stmts = std::mem::take(&mut self.cleanup_stmts);
+            self.deadline = self.script_timeout.map(|timeout| {
+                let message = format!(
+                    "The cleanup section took longer than {}",
+                    format_seconds(timeout)
+                );
+                (Instant::now() + timeout, message)
+            });
+            self.run_stmts(true).await;
+        }
+        self.deadline = None;
+        self.had_error = exited_early;
+
+        // We completed the entire script.
+        if close_driver {
+            tokio::time::timeout(
+                Duration::from_secs(CLOSE_WINDOW_TIMEOUT_SECS),
+                self.driver.close_window(),
+            )
+            .await
+            .context("Timed out closing the browser window")??;
+        }
+
+        // Healing is best effort, so a cache that can't be written doesn't cost the report
+        if let Some(ref cache) = self.locator_cache {
+            if let Err(e) = cache.save() {
+                self.report.warnings.push(format!("{:#}", e));
+            }
+        }
+
+        self.report.exited_early = exited_early;
+        Ok(self.report)
+    }
+
+    /// Executes the stmts on the stack and reports them, until they run out or a failure
+    /// ends the script. Every failure in the cleanup section is soft.
+    async fn run_stmts(&mut self, cleanup: bool) {
         while let Some(stmt) = self.stmts.pop() {
-            match self.execute_stmt(stmt.clone()).await {
-                Ok(()) => {
-                    self.report.executed_stmts.push(ExecutedStmt {
-                        text: stmt.to_string(),
-                        error: None,
-                        screenshots: std::mem::take(&mut self.screenshot_buffer),
-                    });
+            // Statements skipped while looking for a catch-error stmt didn't run, so they're reported as skipped
+            let skipped = self.had_error
+                && !matches!(stmt, Stmt::CatchErr(ref kinds, _) if self.handles(kinds));
+            let soft = cleanup || self.keep_going || matches!(stmt, Stmt::Soft(_));
+            let retry = self.retry;
+            let res = self.execute_stmt_in_time(stmt.clone()).await;
+
+            // report the statement, and its error if it had one, with what the page looked like
+            let error = match skipped {
+                true => None,
+                false => res.as_ref().err().map(|e| e.to_string()),
+            };
+            let artifacts = match error {
+                Some(_) => Some(
+                    tokio::time::timeout(
+                        Duration::from_secs(ARTIFACTS_TIMEOUT_SECS),
+                        self.failure_artifacts(),
+                    )
+                    .await
+                    .unwrap_or_default(),
+                ),
+                None => None,
+            };
+            self.report.executed_stmts.push(ExecutedStmt {
+                text: stmt.to_string(),
+                error,
+                screenshots: std::mem::take(&mut self.screenshot_buffer),
+                downloads: std::mem::take(&mut self.download_buffer),
+                warnings: std::mem::take(&mut self.warning_buffer),
+                notes: std::mem::take(&mut self.note_buffer),
+                healed: std::mem::take(&mut self.healed_buffer),
+                retry: (retry > 0).then_some(retry),
+                artifacts,
+                cleanup,
+                skipped,
+            });
+            if skipped {
+                continue;
+            }
+
+            if let Err(e) = res {
+                // Once the script's time is up, nothing can handle the failure
+                if self
+                    .deadline
+                    .as_ref()
+                    .is_some_and(|(deadline, _)| Instant::now() >= *deadline)
+                {
+                    self.had_error = true;
+                    self.error_kind = Some(ErrorKind::Timeout);
+                    break;
                 }
-                Err(e) => {
-                    // report the error
-                    self.report.executed_stmts.push(ExecutedStmt {
-                        text: stmt.to_string(),
-                        error: Some(e.to_string()),
-                        screenshots: std::mem::take(&mut self.screenshot_buffer),
-                    });
 
-                    match self.had_error {
-                        true => break,
-                        false => self.had_error = true,
+                // Soft failures are only recorded
+                if soft && !self.had_error {
+                    continue;
+                }
+
+                match self.had_error {
+                    true => break,
+                    false => {
+                        self.had_error = true;
+                        self.error_kind = ErrorKind::of(&e);
                     }
                 }
             }
         }
+    }
 
-        // We completed the entire script.
-        if close_driver {
-            self.driver.close_window().await?;
+    /// Executes a single statement, failing with a timeout if it runs longer than the
+    /// statement-timeout setting or past the script's deadline.
+    pub async fn execute_stmt_in_time(&mut self, stmt: Stmt) -> Result<()> {
+        let statement_limit = self.settings.statement_timeout.map(|limit| {
+            let message = format!("The statement took longer than {}", format_seconds(limit));
+            (limit, message)
+        });
+        let script_limit = self.deadline.as_ref().map(|(deadline, message)| {
+            (
+                deadline.saturating_duration_since(Instant::now()),
+                message.clone(),
+            )
+        });
+        let (limit, message) = match (statement_limit, script_limit) {
+            (Some(statement), Some(script)) => std::cmp::min_by_key(statement, script, |l| l.0),
+            (Some(limit), None) | (None, Some(limit)) => limit,
+            (None, None) => {
+                self.update_browser_timeouts().await?;
+                return self.execute_stmt(stmt).await;
+            }
+        };
+
+        // What the statement may change part way through, to put back if it's cut off
+        let scopes = self.scopes.clone();
+        let current_element = self.current_element.clone();
+        let last_used_locator = self.last_used_locator.clone();
+
+        // Dropping a statement that runs out of time doesn't stop the browser, so the browser
+        // is also told to give up on page loads and scripts after the statement timeout
+        let timed = async {
+            self.update_browser_timeouts().await?;
+            self.execute_stmt(stmt.clone()).await
+        };
+        match tokio::time::timeout(limit, timed).await {
+            Ok(res) => res,
+            Err(_) => {
+                // The statement was cut off part way, so undo what it may have left behind
+                self.under_element = None;
+                self.scopes = scopes;
+                self.current_element = current_element;
+                self.last_used_locator = last_used_locator;
+                if let Stmt::Within(locator) = stmt {
+                    // Like a failed within, the scope is kept so its end has a scope to close
+                    self.scopes.push(Scope {
+                        locator,
+                        element: None,
+                    });
+                }
+                bail!(ScriptError::new(ErrorKind::Timeout, message))
+            }
         }
+    }
 
-        // If had_error is still true when we exit, it means we had to do an early exit
-        self.report.exited_early = self.had_error;
-        Ok(self.report)
+    /// Set the browser's page load and script timeouts to the statement timeout, if it has
+    /// changed since they were last set.
+    async fn update_browser_timeouts(&mut self) -> Result<()> {
+        if self.browser_timeout == self.settings.statement_timeout {
+            return Ok(());
+        }
+        let timeout = self.settings.statement_timeout;
+        let timeouts = TimeoutConfiguration::new(
+            Some(timeout.unwrap_or(Duration::from_secs(DEFAULT_SCRIPT_TIMEOUT_SECS))),
+            Some(timeout.unwrap_or(Duration::from_secs(DEFAULT_PAGE_LOAD_TIMEOUT_SECS))),
+            None,
+        );
+        self.driver
+            .update_timeouts(timeouts)
+            .await
+            .context("Could not set the browser's page load and script timeouts")?;
+        self.browser_timeout = timeout;
+        Ok(())
+    }
+
+    /// Capture what the page looked like when a statement failed: a screenshot (added to the
+    /// screenshot buffer), the url, the title and the page source. Best effort, since the
+    /// browser may be what failed.
+    async fn failure_artifacts(&mut self) -> FailureArtifacts {
+        if let Ok(ss) = self.driver.screenshot_as_png().await {
+            self.screenshot_buffer.push(ss);
+        }
+        FailureArtifacts {
+            url: self
+                .driver
+                .current_url()
+                .await
+                .ok()
+                .map(|url| url.to_string()),
+            title: self.driver.title().await.ok(),
+            page_source: self.driver.source().await.ok(),
+        }
     }
 
     /// Takes a webelement, attempts to scroll the element into view, and then sets
@@ -181,7 +545,7 @@ impl Interpreter {
                 // Element is stale, so replay the last locate command. Helps with pages which are highly dynamic
                 // for a few moments during the loading.
                 if let Some(locator) = self.last_used_locator.clone() {
-                    let _ = self.locate(CmdParam::String(locator), false).await?;
+                    let _ = self.locate(locator, false).await?;
                 }
             }
         }
@@ -192,42 +556,59 @@ impl Interpreter {
     }
 
     /// Executes a single SchnauzerUI statement.
-    pub async fn execute_stmt(&mut self, stmt: Stmt) -> Result<()> {
+    pub async fn execute_stmt(&mut self, mut stmt: Stmt) -> Result<()> {
         // Add the statement to the list of stmts since the last catch-error stmt was encountered.
         // Used by the try-again command to re-execute on an error.
+        if self.statements_since_last_error_handling.is_empty() {
+            self.scope_depth_at_last_error_handling = self.scopes.len();
+        }
         self.statements_since_last_error_handling.push(stmt.clone());
+        self.current_stmt = stmt.to_string();
+
+        // Whether a failure is soft is up to `interpret`, the statement itself runs as usual
+        while let Stmt::Soft(inner) = stmt {
+            stmt = *inner;
+        }
 
         if !self.had_error {
             // Normal Execution
             match stmt {
-                Stmt::Cmd(cs) => self.execute_cmd_stmt(cs).await,
-                Stmt::If(is) => self.execute_if_stmt(is).await,
+                Stmt::Cmd(cs) => {
+                    let res = self.execute_cmd_stmt(cs).await;
+
+                    // A locate-row scope only lasts for the rest of its statement
+                    self.under_element = None;
+                    res
+                }
+                Stmt::If(is) => {
+                    let res = self.execute_if_stmt(is).await;
+                    self.under_element = None;
+                    res
+                }
                 Stmt::SetVariable(sv) => {
                     self.set_variable(sv);
                     Ok(())
                 }
+                Stmt::Setting(ss) => self.apply_setting(ss),
                 Stmt::Comment(_) => {
                     // Comments are simply added to the report log, so we just ignore them
                     Ok(())
                 }
-                Stmt::CatchErr(_) => {
+                Stmt::CatchErr(..) => {
                     // If we hit a catch-error stmt but no error occured, we dont do anything.
                     // Clear statements since last error so try-again command doesnt re-execute the entire script.
+                    // If they were being retried, the retry passed.
                     self.statements_since_last_error_handling.clear();
+                    self.retry = 0;
                     Ok(())
                 }
-                Stmt::SetHadErrorFieldToFalse => {
-                    // This command was inserted by the interpreter as part of executing try-again.
-                    // Reaching this command means the second attempt passed without erroring,
-                    // so we go back to normal execution mode.
-                    self.had_error = false;
-                    Ok(())
-                }
-                Stmt::Under(cp, cs) => {
-                    self.under_element = Some(self.locate(cp, true).await?);
-                    self.execute_cmd_stmt(cs).await?;
+                Stmt::Under(locator, cs) => {
+                    self.under_element = Some(self.locate(locator, true).await?);
+                    let res = self.execute_cmd_stmt(cs).await;
+
+                    // The scope ends with the statement, even if it failed
                     self.under_element = None;
-                    Ok(())
+                    res
                 }
                 Stmt::UnderActiveElement(cs) => {
                     let active_elm = self
@@ -236,31 +617,77 @@ impl Interpreter {
                         .await
                         .context("Error getting active element.")?;
                     self.under_element = Some(active_elm);
-                    self.execute_cmd_stmt(cs).await?;
+                    let res = self.execute_cmd_stmt(cs).await;
                     self.under_element = None;
+                    res
+                }
+                Stmt::Within(locator) => {
+                    // The scope is kept even if the locate fails, so its end has a scope to close
+                    let res = self.locate(locator.clone(), true).await;
+                    self.scopes.push(Scope {
+                        locator,
+                        element: res.as_ref().ok().cloned(),
+                    });
+                    res.map(|_| ())
+                }
+                Stmt::Soft(_) => unreachable!("soft statements are unwrapped above"),
+                Stmt::Timeout(cp) => {
+                    let value = self.resolve(cp)?;
+                    self.settings.set("statement-timeout", &value)
+                }
+                Stmt::Cleanup => {
+                    // `interpret` runs the cleanup section on its own. Elsewhere, like in the repl,
+                    // there's nothing to do.
+                    Ok(())
+                }
+                Stmt::End => {
+                    self.scopes
+                        .pop()
+                        .context("Found an end without a within block to close")?;
                     Ok(())
                 }
             }
         } else {
             // Syncronizing after an error.
             match stmt {
-                Stmt::CatchErr(cs) => {
-                    // Execute the commands on the catch-error line.
-                    self.execute_cmd_stmt(cs).await?;
+                Stmt::CatchErr(kinds, cs) if self.handles(&kinds) => {
+                    // Execute the commands on the catch-error line. The statements since the last
+                    // one have been handled, so a later try-again doesn't re-execute them.
+                    let res = self.execute_cmd_stmt(cs).await;
+                    self.statements_since_last_error_handling.clear();
+                    res?;
 
                     // Exit error mode and continue normal operation.
                     self.had_error = false;
                     Ok(())
                 }
-                stmt => {
-                    // Read in the rest of the stmts until catch-error for possible re-execution.
-                    self.statements_since_last_error_handling.push(stmt);
+                Stmt::Within(locator) => {
+                    // Keep track of skipped blocks so the ends after them close the right scope
+                    self.scopes.push(Scope {
+                        locator,
+                        element: None,
+                    });
+                    Ok(())
+                }
+                Stmt::End => {
+                    self.scopes.pop();
+                    Ok(())
+                }
+                _ => {
+                    // The rest of the stmts until catch-error are skipped. They were read in
+                    // above for possible re-execution.
                     Ok(())
                 }
             }
         }
     }
 
+    /// Whether a catch-error stmt for the given kinds of error handles the error the script
+    /// encountered. A catch-error stmt which doesn't name any kinds handles every error.
+    fn handles(&self, kinds: &[ErrorKind]) -> bool {
+        kinds.is_empty() || self.error_kind.is_some_and(|kind| kinds.contains(&kind))
+    }
+
     /// Sets the value of a variable.
     fn set_variable(
         &mut self,
@@ -272,11 +699,65 @@ impl Interpreter {
         self.environment.set_variable(variable_name, value);
     }
 
+    /// Changes one of the interpreter's settings.
+    fn apply_setting(&mut self, SettingStmt { name, value }: SettingStmt) -> Result<()> {
+        let value = self.resolve(value)?;
+        self.settings.set(&name, &value)
+    }
+
     /// Tries to retrieve the value of a variable.
     fn get_variable(&self, name: &str) -> Result<String> {
-        self.environment
-            .get_variable(name)
-            .context("Variable is not yet defined")
+        match self.environment.get_value(name) {
+            Some(Value::Text(txt)) => Ok(txt.clone()),
+            Some(Value::Table(_)) => bail!(
+                "{} is a table. Pick a cell with a row number and column header, like {}[1][\"Name\"]",
+                name,
+                name
+            ),
+            None => bail!("Variable is not yet defined"),
+        }
+    }
+
+    /// Tries to retrieve the text at an index of a structured variable, like
+    /// a table cell with `results[2]["Status"]`.
+    fn get_indexed_variable(&self, name: &str, indices: &[Index]) -> Result<String> {
+        let table = match self.environment.get_value(name) {
+            Some(Value::Table(table)) => table,
+            Some(Value::Text(_)) => bail!("{} is text, so it can't be indexed", name),
+            None => bail!("Variable is not yet defined"),
+        };
+
+        let [Index::Position(row_number), column] = indices else {
+            bail!(
+                "Pick a table cell with a row number and a column, like {}[1][\"Name\"]",
+                name
+            )
+        };
+        let row = table.row(*row_number).with_context(|| {
+            format!(
+                "{} only has {} rows, so there is no row {}",
+                name,
+                table.rows.len(),
+                row_number
+            )
+        })?;
+        let column_position = match column {
+            Index::Position(n) => n - 1,
+            Index::Key(header) => table.column(header).with_context(|| {
+                format!(
+                    "{} has no column \"{}\". The columns are: {}",
+                    name,
+                    header,
+                    table.headers.join(", ")
+                )
+            })?,
+        };
+        row.get(column_position).cloned().with_context(|| {
+            format!(
+                "Row {} of {} has no cell in column {}",
+                row_number, name, column
+            )
+        })
     }
 
     /// Takes a cmd_param and tries to resolve it to a string. If it's a user provided String literal, just
@@ -286,6 +767,7 @@ impl Interpreter {
         match cmd_param {
             CmdParam::String(s) => Ok(s),
             CmdParam::Variable(v) => self.get_variable(&v),
+            CmdParam::Indexed(v, indices) => self.get_indexed_variable(&v, &indices),
         }
     }
 
@@ -319,20 +801,20 @@ impl Interpreter {
 
     /// Execute a single Schnauzer UI command
     async fn execute_cmd(&mut self, cmd: Cmd) -> Result<()> {
-        // Adding a default wait of 1 second between commands because it just mimics human timing a lot
-        // better. Will add a flag to turn this off.
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        // In demo mode, wait a second between commands so a person can follow along.
+        // Otherwise commands that need to wait for the page (like locate) poll for it.
+        if self.is_demo {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
 
         match cmd {
             Cmd::Locate(locator) => self.locate(locator, true).await.map(|_| ()),
             Cmd::LocateNoScroll(locator) => self.locate(locator, false).await.map(|_| ()),
+            Cmd::LocateRow(column, value) => self.locate_row(column, value).await,
             Cmd::Type(txt) => self.type_into_elem(txt).await,
             Cmd::Click => self.click().await,
             Cmd::Refresh => self.refresh().await,
-            Cmd::TryAgain => {
-                self.try_again();
-                Ok(())
-            }
+            Cmd::TryAgain(retries, backoff) => self.try_again(retries, backoff).await,
             Cmd::Screenshot => self.screenshot().await,
             Cmd::ReadTo(cp) => self.read_to(cp).await,
             Cmd::Url(url) => self.url_cmd(url).await,
@@ -340,7 +822,11 @@ impl Interpreter {
             Cmd::Chill(cp) => self.chill(cp).await,
             Cmd::Select(cp) => self.select(cp).await,
             Cmd::DragTo(cp) => self.drag_to(cp).await,
-            Cmd::Upload(cp) => self.upload(cp).await,
+            Cmd::DragBy(x, y) => self.drag_by(x, y).await,
+            Cmd::ReadTableTo(name) => self.read_table_to(name).await,
+            Cmd::Upload(cps) => self.upload(cps).await,
+            Cmd::ExpectDownload(cp) => self.expect_download(cp).await,
+            Cmd::ReadDownloadTo(name) => self.read_download_to(name),
             Cmd::AcceptAlert => self
                 .driver
                 .accept_alert()
@@ -354,31 +840,105 @@ impl Interpreter {
         }
     }
 
-    // Very often a user will locate an html label element and then
-    // specify a command intending to interact with it's associated
-    // input element. We'll try to aid this convenient behavior by dynamically
-    // swapping out the label for its input as the current element.
-    // A label/input pair with the matching for/id attributes respectively,
-    // or a label/input pair where the label element contains the input element or directly precedes it,
-    // will be swapped.
-    // Note: Making a change to include span in this, as people tend to use those too.
-    async fn resolve_label_or_span(&mut self) -> Result<()> {
-        // Label with correct for attribute
-        let tag_name = self
-            .get_curr_elem()
-            .await?
-            .tag_name()
+    /// Very often a user will locate a label and then specify a command intending to interact
+    /// with the control it labels. We aid this convenient behavior by swapping out the label
+    /// for its control as the current element (see `FIND_LABELLED_CONTROL` for how the control
+    /// is found), and noting the swap in the report.
+    /// Divs and paragraphs are only treated as labels for commands which fill in a control,
+    /// since a clicked div or paragraph is usually what was meant to be clicked.
+    async fn resolve_label(&mut self, filling_in: bool) -> Result<()> {
+        let label = self.get_curr_elem().await?.clone();
+        let swap = self
+            .driver
+            .execute(
+                FIND_LABELLED_CONTROL,
+                vec![label.to_json()?, serde_json::Value::Bool(filling_in)],
+            )
             .await
-            .unwrap_or("ignore_error".to_owned());
+            .context("Error looking for the control the element labels")?;
+        if swap.json().is_null() {
+            return Ok(());
+        }
+
+        let control =
+            WebElement::from_json(swap.json()["control"].clone(), self.driver.handle.clone())?;
+        let tag = label.tag_name().await.unwrap_or_default();
+        self.note_buffer.push(format!(
+            "Swapped the located {} for {} ({})",
+            tag,
+            swap.json()["description"].as_str().unwrap_or_default(),
+            swap.json()["how"].as_str().unwrap_or_default()
+        ));
+        let _ = self.set_curr_elem(control, false).await?;
+        Ok(())
+    }
+
+    /// Upload one or more files.
+    async fn upload(&mut self, cps: Vec<CmdParam>) -> Result<()> {
+        // Relative paths are written relative to the script, not wherever it happens to be run from
+        let mut paths = vec![];
+        for cp in cps {
+            let path = Utf8PathBuf::from(self.resolve(cp)?);
+            let path = match self.script_directory {
+                Some(ref dir) if path.is_relative() => dir.join(path),
+                _ => path,
+            };
+            paths.push(
+                path.canonicalize_utf8()
+                    .with_context(|| format!("Could not find file to upload: {}", path))?,
+            );
+        }
 
-        if tag_name == "label" || tag_name == "span" {
-            // Label contains input or textarea
+        self.resolve_file_input().await?;
+
+        let input = self.get_curr_elem().await?;
+        if paths.len() > 1 && input.attr("multiple").await?.is_none() {
+            bail!("This file input only accepts one file. Try uploading the files one at a time");
+        }
+
+        // Uploading to a file input is the same as typing keys into it,
+        // but our users shouldn't have to know that. Multiple files are separated by newlines.
+        input
+            .send_keys(
+                paths
+                    .iter()
+                    .map(|path| path.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+            .await
+            .context("Error uploading file")
+    }
+
+    // Custom upload components usually hide the real `<input type="file">` and show
+    // a styled button or drop zone instead. When the located element isn't a file input,
+    // we'll swap in the file input it is associated with: one it contains, one its `for`
+    // attribute points to, or the nearest one found by climbing its parents.
+    async fn resolve_file_input(&mut self) -> Result<()> {
+        let current = self.get_curr_elem().await?.clone();
+        if current.tag_name().await? == "input"
+            && current.attr("type").await?.as_deref() == Some("file")
+        {
+            return Ok(());
+        }
+
+        // File input inside the located element
+        if let Ok(input) = current
+            .query(By::XPath(".//input[@type='file']"))
+            .nowait()
+            .first()
+            .await
+        {
+            let _ = self.set_curr_elem(input, false).await?;
+            return Ok(());
+        }
+
+        // File input the located element is a label for
+        if let Some(for_attr) = current.attr("for").await? {
             if let Ok(input) = self
-                .get_curr_elem()
-                .await?
-                .query(By::Tag("input"))
-                .or(By::Tag("textarea"))
-                .or(By::Tag("select"))
+                .driver
+                .query(By::Id(&for_attr))
+                .or(By::Name(&for_attr))
                 .nowait()
                 .first()
                 .await
@@ -386,116 +946,147 @@ impl Interpreter {
                 let _ = self.set_curr_elem(input, false).await?;
                 return Ok(());
             }
+        }
 
-            // Get the for attribute
-            let for_attr = self.get_curr_elem().await?.attr("for").await?;
-
-            // Try to find the input element with the corresponding id or name attribute
-            if let Some(for_attr) = for_attr {
-                // Try to find the element
-                let label_target = self
-                    .driver
-                    .query(By::Id(&for_attr))
-                    .or(By::Name(&for_attr))
-                    .nowait()
-                    .first()
-                    .await
-                    .ok();
-
-                // If we found an associated element, swap into current element
-                if let Some(target) = label_target {
-                    let _ = self.set_curr_elem(target, false).await?;
-                    return Ok(());
-                }
-            }
-
-            // If the label doesn't contain the input or have an associated for attribute
-            // leading to the input, then check to see if there is an input element right
-            // after the label
-            let following_input = self
-                .get_curr_elem()
-                .await?
-                .query(By::XPath("./following-sibling::input"))
-                .or(By::XPath("./following-sibling::textarea"))
-                .or(By::XPath("./following-sibling::select"))
+        // Nearest file input around the located element. Limited like the label swap,
+        // as a file input further away probably belongs to something else.
+        let mut ancestor = current;
+        for _ in 0..5 {
+            let Ok(parent) = ancestor.find(By::XPath("./..")).await else {
+                break;
+            };
+            if let Ok(input) = parent
+                .query(By::XPath(".//input[@type='file']"))
                 .nowait()
                 .first()
                 .await
-                .ok();
-
-            if let Some(elm) = following_input {
-                let _ = self.set_curr_elem(elm, false).await?;
+            {
+                let _ = self.set_curr_elem(input, false).await?;
                 return Ok(());
             }
+            ancestor = parent;
+        }
 
-            // If none of this works, perform a recursive search for the input element
-            // (like the "under" command but specifically for an input)
-            // Limit to 5 elements of depth b/c anything further is probably a bug.
-            // To do a full recursive search, users can use
-            // `under "<label-text>" locate "input" and type "some text"`
-            for _ in 0..5 {
-                match self.get_curr_elem().await?.find(By::XPath("./..")).await {
-                    Ok(parent) => {
-                        let _ = self.set_curr_elem(parent, false).await?;
-                        match self
-                            .get_curr_elem()
-                            .await?
-                            .query(By::Tag("input"))
-                            .or(By::Tag("textarea"))
-                            .or(By::XPath("select"))
-                            .nowait()
-                            .first()
-                            .await
-                            .ok()
-                        {
-                            Some(elm) => {
-                                let _ = self.set_curr_elem(elm, false).await?;
-                                return Ok(());
-                            }
-                            None => continue,
-                        }
-                    }
-                    Err(_) => break, // the resolve failed but we'll keep going
+        Ok(())
+    }
+
+    /// Wait for the browser to finish downloading a file matching the given pattern.
+    async fn expect_download(&mut self, cp: CmdParam) -> Result<()> {
+        let pattern = self.resolve(cp)?;
+        let dir = self
+            .download_directory
+            .clone()
+            .context("No download directory is configured for this browser")?;
+
+        // A download is finished once it stops being a partial file and its size stops changing
+        let mut last_seen: Option<(Utf8PathBuf, u64)> = None;
+        let polls = DOWNLOAD_TIMEOUT_SECS * 1000 / DOWNLOAD_POLL_MILLIS;
+        for _ in 0..polls {
+            if let Some(path) =
+                find_completed_download(&dir, &pattern, &self.downloads, &self.existing_downloads)?
+            {
+                let size = path.metadata()?.len();
+                if last_seen.as_ref() == Some(&(path.clone(), size)) {
+                    self.downloads.push(path.clone());
+                    self.download_buffer.push(path);
+                    return Ok(());
                 }
+                last_seen = Some((path, size));
             }
+            tokio::time::sleep(Duration::from_millis(DOWNLOAD_POLL_MILLIS)).await;
         }
 
+        bail!(ScriptError::new(
+            ErrorKind::AssertionFailed,
+            format!(
+                "No download matching \"{}\" finished within {} seconds",
+                pattern, DOWNLOAD_TIMEOUT_SECS
+            )
+        ))
+    }
+
+    /// Reads the text of the last expected download to a variable.
+    fn read_download_to(&mut self, name: String) -> Result<()> {
+        let path = self
+            .downloads
+            .last()
+            .context("No file downloaded yet. Try using the expect-download command")?;
+        let txt = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading downloaded file {}", path))?;
+        self.environment.set_variable(name, txt);
         Ok(())
     }
 
-    /// Upload a file.
-    async fn upload(&mut self, cp: CmdParam) -> Result<()> {
-        // Uploading to a file input is the same as typing keys into it,
-        // but our users shouldn't have to know that.
-        let path = Utf8PathBuf::from(self.resolve(cp)?).canonicalize_utf8()?;
+    /// Drag the currently located element to another. Elements which opt into HTML5 drag and drop
+    /// get simulated drag events, everything else is dragged with the mouse (see `DragMode`).
+    async fn drag_to(&mut self, cp: CmdParam) -> Result<()> {
+        let current = self.get_curr_elem().await?.clone();
+        let target = self.locate(cp.into(), false).await?;
 
-        self.get_curr_elem()
-            .await?
-            .send_keys(path)
-            .await
-            .context("Error uploading file")
+        let use_pointer = match self.settings.drag_mode {
+            DragMode::Html5 => false,
+            DragMode::Pointer => true,
+            DragMode::Auto => current.attr("draggable").await?.as_deref() != Some("true"),
+        };
+
+        if use_pointer {
+            let (start_x, start_y) = current.rect().await?.icenter();
+            let (end_x, end_y) = target.rect().await?.icenter();
+            self.pointer_drag(&current, end_x - start_x, end_y - start_y)
+                .await
+        } else {
+            current
+                .handle
+                .execute(
+                    SIMULATE_DRAG_AND_DROP,
+                    vec![current.to_json()?, target.to_json()?],
+                )
+                .await
+                .context("Error dragging element.")?;
+            Ok(())
+        }
     }
 
-    /// Drag the currently located element to another (simulated with js)
-    async fn drag_to(&mut self, cp: CmdParam) -> Result<()> {
+    /// Drag the currently located element by an x and y offset in pixels.
+    async fn drag_by(&mut self, x: CmdParam, y: CmdParam) -> Result<()> {
+        let Ok(x_offset) = self.resolve(x)?.parse::<i64>() else {
+            bail!("Could not parse x offset as integer.")
+        };
+        let Ok(y_offset) = self.resolve(y)?.parse::<i64>() else {
+            bail!("Could not parse y offset as integer.")
+        };
         let current = self.get_curr_elem().await?.clone();
-        let target = self.locate(cp, false).await?;
-        current
-            .handle
-            .execute(
-                SIMULATE_DRAG_AND_DROP,
-                vec![current.to_json()?, target.to_json()?],
-            )
+        self.pointer_drag(&current, x_offset, y_offset).await
+    }
+
+    /// Press the mouse on the center of an element, move it by the offset in several
+    /// small steps, and release it.
+    async fn pointer_drag(&self, elem: &WebElement, x_offset: i64, y_offset: i64) -> Result<()> {
+        let mut chain = self
+            .driver
+            .action_chain()
+            .move_to_element_center(elem)
+            .click_and_hold();
+
+        // Move in steps which add up exactly to the requested offset
+        for step in 0..DRAG_STEPS {
+            let dx = x_offset * (step + 1) / DRAG_STEPS - x_offset * step / DRAG_STEPS;
+            let dy = y_offset * (step + 1) / DRAG_STEPS - y_offset * step / DRAG_STEPS;
+            chain = chain.move_by_offset(dx, dy);
+        }
+
+        chain
+            .release()
+            .perform()
             .await
-            .context("Error dragging element.")?;
-        Ok(())
+            .context("Error dragging element.")
     }
 
     /// Select an option from a select element.
     async fn select(&mut self, cp: CmdParam) -> Result<()> {
         let option_text = self.resolve(cp)?;
 
-        self.resolve_label_or_span().await?;
+        self.resolve_label(true).await?;
 
         // Sometimes, a Select element's only visible text on the page
         // is it's default option. Many users may try to locate
@@ -540,7 +1131,7 @@ impl Interpreter {
             _ => bail!("Could not parse time to wait as integer."),
         };
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(time_to_wait)).await;
+        tokio::time::sleep(Duration::from_secs(time_to_wait)).await;
 
         Ok(())
     }
@@ -570,14 +1161,66 @@ impl Interpreter {
         Ok(())
     }
 
-    /// Re-executes the commands since the last catch-error stmt.
-    fn try_again(&mut self) {
-        self.stmts.push(Stmt::SetHadErrorFieldToFalse);
+    /// Reads the html table (or ARIA grid) containing the currently located element to a variable.
+    async fn read_table_to(&mut self, name: String) -> Result<()> {
+        let elem = self.get_curr_elem().await?.clone();
+        let table = self
+            .driver
+            .execute(
+                &format!("{}{}", TABLE_FUNCTIONS, READ_TABLE),
+                vec![elem.to_json()?],
+            )
+            .await
+            .context("Error reading table")?
+            .convert::<Option<Table>>()
+            .context("Error reading table")?
+            .context("The located element is not part of a table")?;
+        self.environment.set_value(name, Value::Table(table));
+        Ok(())
+    }
 
-        // This would be more efficient with some kind of mem_swap type function.
-        self.stmts
-            .append(&mut self.statements_since_last_error_handling.clone());
-        self.statements_since_last_error_handling.clear();
+    /// Re-executes the commands since the last catch-error stmt, up to the given number of
+    /// times (`DEFAULT_RETRIES` if not given). With a backoff, waits that many seconds before
+    /// the first retry and twice as long before each one after.
+    async fn try_again(
+        &mut self,
+        retries: Option<CmdParam>,
+        backoff: Option<CmdParam>,
+    ) -> Result<()> {
+        let retries = match retries {
+            Some(retries) => {
+                let retries = self.resolve(retries)?;
+                retries.parse::<usize>().with_context(|| {
+                    format!(
+                        "Expected a number of retries like \"3\", found \"{}\"",
+                        retries
+                    )
+                })?
+            }
+            None => DEFAULT_RETRIES,
+        };
+        if self.retry >= retries {
+            bail!(
+                "Still failing after {} {}",
+                retries,
+                if retries == 1 { "retry" } else { "retries" }
+            );
+        }
+        self.retry += 1;
+
+        if let Some(backoff) = backoff {
+            let backoff = parse_seconds(&self.resolve(backoff)?)?;
+            let factor = 2u32.saturating_pow(self.retry as u32 - 1);
+            tokio::time::sleep(backoff.saturating_mul(factor)).await;
+        }
+
+        // The statements are popped off the end, so they go on in reverse. Blocks they opened
+        // are opened again when they re-execute.
+        self.scopes
+            .truncate(self.scope_depth_at_last_error_handling);
+        let stmts = std::mem::take(&mut self.statements_since_last_error_handling);
+        self.stmts.extend(stmts.into_iter().rev());
+        Ok(())
     }
 
     /// Takes a screenshot of the page.
@@ -598,7 +1241,7 @@ impl Interpreter {
 
     /// Tries to click on the currently located web element.
     async fn click(&mut self) -> Result<()> {
-        self.resolve_label_or_span().await?;
+        self.resolve_label(false).await?;
 
         // We need to wait for the element to be clickable by default,
         // but also account for weird htmls structures. So, we'll
@@ -619,7 +1262,7 @@ impl Interpreter {
     async fn type_into_elem(&mut self, cmd_param: CmdParam) -> Result<()> {
         let txt = self.resolve(cmd_param)?;
 
-        self.resolve_label_or_span().await?;
+        self.resolve_label(true).await?;
 
         // Instead of typing into the located element,
         // we'll click the located element, then type
@@ -631,7 +1274,7 @@ impl Interpreter {
 
         // Wait a second in case some javascript needs to happen
         // for fancy components
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        tokio::time::sleep(Duration::from_secs(1)).await;
 
         // Get the active element
         let active_elm = self
@@ -652,307 +1295,496 @@ impl Interpreter {
     /// Navigates to the provided url.
     async fn url_cmd(&mut self, url: CmdParam) -> Result<()> {
         let url = self.resolve(url)?;
-        self.driver
-            .goto(url)
-            .await
-            .context("Error navigating to page.")
+        self.driver.goto(url).await.map_err(|e| {
+            // A page which takes too long to load is a timeout, anything else a navigation error
+            let e = anyhow::Error::from(e);
+            let kind = match ErrorKind::of(&e) {
+                Some(ErrorKind::Timeout) => ErrorKind::Timeout,
+                _ => ErrorKind::Navigation,
+            };
+            e.context(ScriptError::new(kind, "Error navigating to page."))
+        })
     }
 
-    /// Attempt to locate an element on the page, testing the locator in the following precedence
-    /// (placeholder, preceding label, text, id, name, title, class, xpath)
-    #[async_recursion]
-    async fn locate(&mut self, locator: CmdParam, scroll_into_view: bool) -> Result<WebElement> {
-        let locator = self.resolve(locator)?;
+    /// Locate the table row whose cell in the given column has the given text, and search
+    /// from that row for the rest of the statement (like the under command).
+    async fn locate_row(&mut self, column: CmdParam, value: CmdParam) -> Result<()> {
+        let column = self.resolve(column)?;
+        let value = self.resolve(value)?;
 
-        // Store the locator in case we need to re-execute locate command (stale element, etc.)
-        self.last_used_locator = Some(locator.clone());
+        // Search from the under element if there is one, otherwise the within block (or whole document)
+        let root = match self.under_element {
+            Some(ref elem) => elem.to_json()?,
+            None => self.scope_root().await?.to_json()?,
+        };
 
-        // If we're in a state of "under", search from the base element
-        if let Some(ref base_elem) = self.under_element {
-            // Locate an input element by its placeholder
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//input[@placeholder='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
+        let deadline = Instant::now() + self.settings.locate_timeout;
+        loop {
+            if let Ok(row) = self
+                .driver
+                .execute(
+                    &format!("{}{}", TABLE_FUNCTIONS, FIND_TABLE_ROW),
+                    vec![root.clone(), column.clone().into(), value.clone().into()],
+                )
                 .await
+                .context("Error searching tables")?
+                .element()
             {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+                let row = self.set_curr_elem(row, true).await?;
+                self.under_element = Some(row);
+                return Ok(());
             }
-
-            // Try to find the element by partial placeholder
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(
-                    ".//input[contains(@placeholder, '{}')]",
-                    locator
-                )))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+            if !self.wait_to_poll(deadline).await {
+                break;
             }
+        }
 
-            // Try to find the element by its text
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//*[text()='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+        bail!(ScriptError::new(
+            ErrorKind::NotFound,
+            format!("Could not find a row where \"{}\" is \"{}\"", column, value)
+        ))
+    }
 
-            // Try to find the element by partial text
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//*[contains(text(), '{}')]", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+    /// Attempt to locate an element on the page, trying each of the locator strategies in
+    /// order (see `Locators`).
+    #[async_recursion]
+    async fn locate(&mut self, locator: Locator, scroll_into_view: bool) -> Result<WebElement> {
+        // Resolve variables now, so re-locating later finds the same element
+        let text = self.resolve(locator.text)?;
+
+        let query = text.clone();
+        let locator = Locator {
+            role: locator.role,
+            text: CmdParam::String(text),
+            ordinal: match locator.ordinal {
+                Some(Ordinal::Number(n)) => {
+                    Some(Ordinal::Number(CmdParam::String(self.resolve(n)?)))
+                }
+                ordinal => ordinal,
+            },
+            relation: match locator.relation {
+                Some((relation, anchor)) => {
+                    Some((relation, CmdParam::String(self.resolve(anchor)?)))
+                }
+                None => None,
+            },
+            timeout: match locator.timeout {
+                Some(timeout) => Some(CmdParam::String(self.resolve(timeout)?)),
+                None => None,
+            },
+        };
 
-            // Try to find an element by it's title
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//*[@title='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+        let timeout = match locator.timeout {
+            Some(CmdParam::String(ref secs)) => parse_seconds(secs)?,
+            _ => self.settings.locate_timeout,
+        };
+        let deadline = Instant::now() + timeout;
+
+        // If we're in a state of "under", search from the base element and radiate outward
+        // (see `UnderMode`). Bounded searches fail rather than escape to the rest of the page.
+        let mut escaped_under = false;
+        if let Some(base_elem) = self.under_element.clone() {
+            self.last_used_locator = Some(locator.clone());
+            let bounded = self.settings.under_mode == UnderMode::Strict
+                || self.settings.under_radius.is_some();
+            loop {
+                if let Some((elem, levels)) = self.find_under(&base_elem, &query, &locator).await? {
+                    if levels > 0 {
+                        self.note_buffer.push(format!(
+                            "Found {} {} level{} up from the under element",
+                            locator,
+                            levels,
+                            if levels == 1 { "" } else { "s" }
+                        ));
+                    }
+                    return self.located(elem, &locator, scroll_into_view).await;
+                }
+                if !bounded {
+                    escaped_under = true;
+                    break;
+                }
+                if !self.wait_to_poll(deadline).await {
+                    let place = match (self.settings.under_mode, self.settings.under_radius) {
+                        (UnderMode::Strict, _) | (_, Some(0)) => {
+                            "under the under element".to_owned()
+                        }
+                        (_, Some(1)) => "within 1 level of the under element".to_owned(),
+                        (_, Some(levels)) => {
+                            format!("within {} levels of the under element", levels)
+                        }
+                        (_, None) => unreachable!("unbounded under searches don't fail here"),
+                    };
+                    let message = self
+                        .locate_failure(&locator, &query, timeout, Some(place))
+                        .await;
+                    bail!(ScriptError::new(ErrorKind::NotFound, message))
+                }
             }
+        }
 
-            // Try to locate by aria-label
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//*[@aria-label='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+        // Regular queries, retried until the element shows up or we run out of time
+        let root = self.scope_root().await?;
 
-            // Try to find an element by it's id
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".//*[@id='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+        // Store the locator in case we need to re-execute locate command (stale element, etc.).
+        // Done after finding the root, as re-locating a within block's element sets it too.
+        self.last_used_locator = Some(locator.clone());
+        loop {
+            if let Some(elem) = self.find_match(&root, &query, &locator).await? {
+                if escaped_under {
+                    self.note_buffer.push(format!(
+                        "Nothing near the under element matched {}, so it was found by searching the whole {}",
+                        locator,
+                        if self.scopes.is_empty() { "page" } else { "within block" }
+                    ));
+                }
+                return self.located(elem, &locator, scroll_into_view).await;
             }
-
-            // Try to find an element by it's name
-            if let Ok(found_elem) = base_elem
-                .query(By::Name(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+            if !self.wait_to_poll(deadline).await {
+                break;
             }
+        }
 
-            // Try to find an element by it's class
-            if let Ok(found_elem) = base_elem
-                .query(By::ClassName(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+        if let Some(elem) = self.heal(&locator).await {
+            return self.located(elem, &locator, scroll_into_view).await;
+        }
 
-            // Try to find an element by tag name
-            if let Ok(found_elem) = base_elem
-                .query(By::Tag(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+        // Failures in within blocks name the blocks, outermost first
+        let scope = self
+            .scopes
+            .iter()
+            .map(|scope| scope.locator.to_string())
+            .collect::<Vec<_>>();
+        let place = match scope.is_empty() {
+            true => None,
+            false => Some(format!("inside {}", scope.join(" > "))),
+        };
+        let message = self.locate_failure(&locator, &query, timeout, place).await;
+        bail!(ScriptError::new(ErrorKind::NotFound, message))
+    }
+
+    /// Search under the base element of an `under`, then under each of its ancestors in turn,
+    /// up to the `under-radius` setting (or not at all in strict mode) and never past the innermost
+    /// `within` block. Returns the element found and how many levels up from the base it was.
+    async fn find_under(
+        &mut self,
+        base_elem: &WebElement,
+        query: &str,
+        locator: &Locator,
+    ) -> Result<Option<(WebElement, usize)>> {
+        let max_levels = match self.settings.under_mode {
+            UnderMode::Strict => 0,
+            UnderMode::Radiate => self.settings.under_radius.unwrap_or(usize::MAX),
+        };
+        let scope = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.element.as_ref())
+            .map(|elem| elem.element_id());
+
+        let mut root = base_elem.clone();
+        for levels in 0..=max_levels {
+            let found = self
+                .find_match(&SearchRoot::Element(root.clone()), query, locator)
+                .await?;
+            if let Some(elem) = found {
+                return Ok(Some((elem, levels)));
+            }
+            if levels == max_levels || Some(root.element_id()) == scope {
+                break;
             }
 
-            // Try to find an element by xpath
-            if let Ok(found_elem) = base_elem
-                .query(By::XPath(&format!(".{}", locator)))
-                .nowait()
-                .first()
+            // Go up one (out of a shadow root to its host, if need be)
+            root = match self
+                .driver
+                .execute(PARENT_ELEMENT, vec![root.to_json()?])
                 .await
+                .map(|ret| ret.element())
             {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
-
-            // If we don't find it under the under elem,
-            // go up one
-            self.under_element = base_elem.find(By::XPath("./..")).await.ok();
-            return self
-                .locate(CmdParam::String(locator), scroll_into_view)
-                .await;
+                Ok(Ok(parent)) => parent,
+                // We've reached the top of the page
+                _ => break,
+            };
         }
+        Ok(None)
+    }
 
-        // Regular queries
-        for wait in [0, 5, 10, 20, 30] {
-            std::thread::sleep(std::time::Duration::from_secs(wait));
+    /// Where searches start: the element of the innermost `within` block, or the whole page
+    /// outside of them. The element is located again if it went stale (or was never found).
+    #[async_recursion]
+    async fn scope_root(&mut self) -> Result<SearchRoot> {
+        let Some(scope) = self.scopes.pop() else {
+            return Ok(SearchRoot::Document(self.driver.handle.clone()));
+        };
 
-            // Locate an input element by its placeholder
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!("//input[@placeholder='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+        // While the scope is popped, locating searches the blocks around it
+        let element = match scope.element {
+            Some(elem) if elem.is_present().await.unwrap_or(false) => Ok(elem),
+            _ => {
+                let current_element = self.current_element.clone();
+                let last_used_locator = self.last_used_locator.clone();
+                let res = self.locate(scope.locator.clone(), false).await;
+                self.current_element = current_element;
+                self.last_used_locator = last_used_locator;
+                res
             }
+        };
+        self.scopes.push(Scope {
+            locator: scope.locator,
+            element: element.as_ref().ok().cloned(),
+        });
+        element.map(SearchRoot::Element)
+    }
 
-            // Try to find the element by partial placeholder
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!(
-                    "//input[contains(@placeholder, '{}')]",
-                    locator
-                )))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+    /// The key the locator cache stores the element a locator found under.
+    fn cache_key(&self, locator: &Locator) -> String {
+        format!("{} | {}", self.current_stmt, locator)
+    }
 
-            // Try to find the element by its text
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!("//*[text()='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+    /// Make the element a locator found the current element, and remember
+    /// what it looks like in the locator cache.
+    async fn located(
+        &mut self,
+        elem: WebElement,
+        locator: &Locator,
+        scroll_into_view: bool,
+    ) -> Result<WebElement> {
+        if self.locator_cache.is_some() {
+            let key = self.cache_key(locator);
+            if let Some(fingerprint) = self.fingerprint(&elem).await {
+                if let Some(ref mut cache) = self.locator_cache {
+                    cache.insert(key, fingerprint);
+                }
             }
+        }
+        self.set_curr_elem(elem, scroll_into_view).await
+    }
 
-            // Try to find the element by partial text
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!("//*[contains(text(), '{}')]", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+    /// Describe an element for the locator cache. Best effort, since the
+    /// cache shouldn't make a locate fail.
+    async fn fingerprint(&self, elem: &WebElement) -> Option<Fingerprint> {
+        self.driver
+            .execute(
+                &format!("{}{}{}", DOM_FUNCTIONS, FINGERPRINT_FUNCTIONS, FINGERPRINT),
+                vec![elem.to_json().ok()?],
+            )
+            .await
+            .ok()?
+            .convert::<Fingerprint>()
+            .ok()
+    }
 
-            // Try to find an element by it's title
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!("//*[@title='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+    /// Find the element on the page most like the one the locator found on an earlier run,
+    /// and record in the report that the locator was healed.
+    async fn heal(&mut self, locator: &Locator) -> Option<WebElement> {
+        let fingerprint = self
+            .locator_cache
+            .as_ref()?
+            .get(&self.cache_key(locator))?
+            .clone();
 
-            // Try to locate by aria-label
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&format!("//*[@aria-label='{}']", locator)))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+        let ret = self
+            .driver
+            .execute(
+                &format!(
+                    "{}{}{}",
+                    DOM_FUNCTIONS, FINGERPRINT_FUNCTIONS, FINGERPRINT_CANDIDATES
+                ),
+                vec![],
+            )
+            .await
+            .ok()?;
+        let candidates =
+            serde_json::from_value::<Vec<Fingerprint>>(ret.json()["fingerprints"].clone()).ok()?;
+        let best = best_match(&fingerprint, &candidates)?;
+        let elem = WebElement::from_json(
+            ret.json()["elements"][best].clone(),
+            self.driver.handle.clone(),
+        )
+        .ok()?;
+
+        self.healed_buffer.push(HealedLocator {
+            locator: locator.to_string(),
+            suggestion: candidates[best].suggested_locator(),
+        });
+        Some(elem)
+    }
 
-            // Try to find an element by it's id
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::Id(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+    /// Find the element a (resolved) locator refers to under the root, or None if it isn't there (yet).
+    async fn find_match(
+        &mut self,
+        root: &SearchRoot,
+        query: &str,
+        locator: &Locator,
+    ) -> Result<Option<WebElement>> {
+        // A role picks the strategy for that role
+        let found = match locator.role {
+            Some(ref role) => self.settings.locators.find_with(role, root, query).await,
+            None => self.settings.locators.find(root, query).await,
+        };
+        let Some(mut found) = found else {
+            return Ok(None);
+        };
 
-            // Try to find an element by it's name
-            if let Ok(found_elem) = self
+        // Keep the matches in the right direction from the anchor, closest first
+        if let Some((relation, CmdParam::String(ref anchor))) = locator.relation {
+            let Some(anchor) = self.settings.locators.find(root, anchor).await else {
+                return Ok(None);
+            };
+            let elements = std::iter::once(&anchor.elements[0])
+                .chain(found.elements.iter())
+                .map(|elem| elem.to_json())
+                .collect::<WebDriverResult<Vec<_>>>()?;
+            let rects = self
                 .driver
-                .query(By::Name(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
+                .execute(ELEMENT_RECTS, vec![serde_json::Value::Array(elements)])
                 .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+                .context("Error measuring elements")?
+                .convert::<Vec<Rect>>()
+                .context("Error measuring elements")?;
 
-            // Try to find an element by it's class
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::ClassName(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+            let ranked = rank_by_relation(relation, &rects[0], &rects[1..]);
+            if ranked.is_empty() {
+                return Ok(None);
             }
+            found.elements = ranked
+                .into_iter()
+                .map(|i| found.elements[i].clone())
+                .collect();
+        }
 
-            // Try to find an element by tag name
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::Tag(&locator))
-                .and_displayed()
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
-            }
+        self.pick_match(found, locator).await.map(Some)
+    }
 
-            // Try to find an element by xpath
-            if let Ok(found_elem) = self
-                .driver
-                .query(By::XPath(&locator))
-                .nowait()
-                .first()
-                .await
-            {
-                return self.set_curr_elem(found_elem, scroll_into_view).await;
+    /// Explain why a locate failed: where it searched (when the search was limited, like to a
+    /// `within` block), which strategies were tried, and what on the page looks like what the
+    /// locator was meant to match.
+    async fn locate_failure(
+        &mut self,
+        locator: &Locator,
+        query: &str,
+        timeout: Duration,
+        place: Option<String>,
+    ) -> String {
+        // The locator's own timeout, if it has one, is the timeout given here
+        let without_timeout = Locator {
+            timeout: None,
+            ..locator.clone()
+        };
+        let mut message = format!(
+            "Could not locate {}{} within {}. Tried strategies: {}.",
+            without_timeout,
+            place.map(|place| format!(" {}", place)).unwrap_or_default(),
+            format_seconds(timeout),
+            match locator.role {
+                Some(ref role) => role.clone(),
+                None => self.settings.locators.strategies_for(query).join(", "),
             }
+        );
 
-            // Try to find the element by any related contents whatsoever.
-            if let Ok(containing_list) = self
-                .driver
-                .query(By::XPath(&format!("//*[contains(., '{}')]", locator)))
-                .and_displayed()
-                .nowait()
-                .all()
-                .await
-            {
-                if let Some(elm) = containing_list.last() {
-                    return self.set_curr_elem(elm.to_owned(), scroll_into_view).await;
-                }
+        // The explanation is best effort, it shouldn't hide the original failure.
+        // Suggestions come from inside the innermost within block.
+        let root = match self.scopes.last().and_then(|scope| scope.element.as_ref()) {
+            Some(elem) => elem.to_json().unwrap_or_default(),
+            None => serde_json::Value::Null,
+        };
+        let candidates = match self
+            .driver
+            .execute(
+                &format!("{}{}{}", DOM_FUNCTIONS, ROLE_FUNCTIONS, LOCATOR_CANDIDATES),
+                vec![root],
+            )
+            .await
+        {
+            Ok(ret) => ret.convert::<Vec<Candidate>>().unwrap_or_default(),
+            Err(_) => vec![],
+        };
+        let suggestions = closest_matches(
+            &self.resolve(locator.text.clone()).unwrap_or_default(),
+            &candidates,
+            3,
+        )
+        .into_iter()
+        .map(|candidate| candidate.to_string())
+        .collect::<Vec<_>>();
+        if let Some((last, rest)) = suggestions.split_last() {
+            if rest.is_empty() {
+                message.push_str(&format!(" Did you mean {}?", last));
+            } else {
+                message.push_str(&format!(" Did you mean {} or {}?", rest.join(", "), last));
             }
         }
+        message
+    }
 
-        bail!("Could not locate the element")
+    /// Wait the poll interval before looking for an element again, but not past the deadline.
+    /// Returns false once the deadline has passed and it's time to give up.
+    async fn wait_to_poll(&self, deadline: Instant) -> bool {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        tokio::time::sleep(self.settings.poll_interval.min(deadline - now)).await;
+        true
+    }
+
+    /// Choose which of the elements a locator matched to use. An ordinal picks one explicitly,
+    /// otherwise the first is used and a match on several displayed elements is reported
+    /// (or fails, see `AmbiguityMode`).
+    async fn pick_match(&mut self, found: LocatorMatch, locator: &Locator) -> Result<WebElement> {
+        let count = found.elements.len();
+        let index = match locator.ordinal {
+            Some(Ordinal::Last) => count - 1,
+            Some(Ordinal::Number(ref n)) => {
+                let n = self.resolve(n.clone())?;
+                match n.parse::<usize>() {
+                    Ok(number) if (1..=count).contains(&number) => number - 1,
+                    Ok(number) => bail!(ScriptError::new(
+                        ErrorKind::NotFound,
+                        format!(
+                            "Asked for match number {} of {}, but only found {} (by {})",
+                            number, locator.text, count, found.strategy
+                        )
+                    )),
+                    Err(_) => bail!("Expected a match number like \"2\", found \"{}\"", n),
+                }
+            }
+            // A relation already ranks the matches by how well they fit
+            None if locator.relation.is_some() => 0,
+            None if count == 1 => 0,
+            None => {
+                // Strategies like xpath and css also match hidden elements, which don't count.
+                // They're counted in one call, since this runs on every poll.
+                let elements = found
+                    .elements
+                    .iter()
+                    .map(|elem| elem.to_json())
+                    .collect::<WebDriverResult<Vec<_>>>()?;
+                let displayed = self
+                    .driver
+                    .execute(
+                        &format!("{}{}", DOM_FUNCTIONS, COUNT_DISPLAYED),
+                        vec![serde_json::Value::Array(elements)],
+                    )
+                    .await
+                    .context("Error checking which matches are displayed")?
+                    .convert::<usize>()
+                    .context("Error checking which matches are displayed")?;
+
+                if displayed > 1 {
+                    let message = format!(
+                        "{} matched {} elements (by {}). Try adding number \"n\" or last to pick one",
+                        locator, displayed, found.strategy
+                    );
+                    match self.settings.ambiguous_locators {
+                        AmbiguityMode::Warn => self.warning_buffer.push(message),
+                        AmbiguityMode::Fail => {
+                            bail!(ScriptError::new(ErrorKind::Ambiguous, message))
+                        }
+                    }
+                }
+                0
+            }
+        };
+        Ok(found.elements[index].clone())
     }
 }
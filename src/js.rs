@@ -47,3 +47,462 @@ function simulateDragDrop(sourceNode, destinationNode) {
 }
 
 simulateDragDrop(arguments[0], arguments[1]);"#;
+
+/// Javascript functions for reading html tables and ARIA grids. Prepend these
+/// to `READ_TABLE` or `FIND_TABLE_ROW` before executing them.
+pub const TABLE_FUNCTIONS: &str = r#"
+var TABLE_SELECTOR = 'table, [role="table"], [role="grid"], [role="treegrid"]';
+
+function cellText(cell) {
+    return (cell.innerText || cell.textContent || '').replace(/\s+/g, ' ').trim();
+}
+
+function isHtmlTable(table) {
+    return table.tagName.toLowerCase() === 'table';
+}
+
+// Only consider rows belonging to this table, not to tables nested inside it
+function rowsOf(table) {
+    return Array.from(isHtmlTable(table) ? table.rows : table.querySelectorAll('[role="row"]'))
+        .filter(function(row) { return row.closest(TABLE_SELECTOR) === table; });
+}
+
+function cellsOf(table, row) {
+    return Array.from(isHtmlTable(table)
+        ? row.cells
+        : row.querySelectorAll('[role="columnheader"], [role="rowheader"], [role="cell"], [role="gridcell"]'));
+}
+
+// The header row is the first row in a thead, or the first row made only of headers
+function headerRowOf(table) {
+    var rows = rowsOf(table);
+    return rows.find(function(row) {
+        var cells = cellsOf(table, row);
+        return (row.parentElement && row.parentElement.tagName.toLowerCase() === 'thead')
+            || (cells.length > 0 && cells.every(function(cell) {
+                return cell.tagName.toLowerCase() === 'th' || cell.getAttribute('role') === 'columnheader';
+            }));
+    }) || rows[0];
+}
+
+// Returns `{ headers: [...], rows: [[...], ...] }` for the table containing (or contained by) the element
+function readTable(elem) {
+    var table = elem.closest(TABLE_SELECTOR) || elem.querySelector(TABLE_SELECTOR);
+    if (!table) {
+        return null;
+    }
+    var headerRow = headerRowOf(table);
+    var headers = headerRow ? cellsOf(table, headerRow).map(cellText) : [];
+    var body = rowsOf(table).filter(function(row) { return row !== headerRow; }).map(function(row) {
+        return cellsOf(table, row).map(cellText);
+    });
+    return { headers: headers, rows: body };
+}
+
+// Returns the first row under root whose cell in the column with the given header has the given text
+function findTableRow(root, header, value) {
+    var tables = Array.from(root.querySelectorAll(TABLE_SELECTOR));
+    if (root.matches && root.matches(TABLE_SELECTOR)) {
+        tables.unshift(root);
+    }
+    for (var i = 0; i < tables.length; i++) {
+        var table = tables[i];
+        var headerRow = headerRowOf(table);
+        if (!headerRow) {
+            continue;
+        }
+        var column = cellsOf(table, headerRow).map(cellText).indexOf(header);
+        if (column === -1) {
+            continue;
+        }
+        var row = rowsOf(table).find(function(row) {
+            var cell = cellsOf(table, row)[column];
+            return row !== headerRow && cell && cellText(cell) === value;
+        });
+        if (row) {
+            return row;
+        }
+    }
+    return null;
+}
+"#;
+
+/// Reads the table containing the element passed as the first argument. Requires `TABLE_FUNCTIONS`.
+pub const READ_TABLE: &str = "return readTable(arguments[0]);";
+
+/// Finds the table row under the element passed as the first argument (or the document
+/// if it's null) whose cell under the header in the second argument has the text in the third.
+/// Requires `TABLE_FUNCTIONS`.
+pub const FIND_TABLE_ROW: &str =
+    "return findTableRow(arguments[0] || document, arguments[1], arguments[2]);";
+
+/// Functions for searching the page, including inside the open shadow roots of web components.
+pub const DOM_FUNCTIONS: &str = r#"
+function normalize(text) {
+    return (text || '').replace(/\s+/g, ' ').trim();
+}
+
+function isDisplayed(el) {
+    return !!(el.offsetWidth || el.offsetHeight || el.getClientRects().length)
+        && getComputedStyle(el).visibility !== 'hidden';
+}
+
+// Every element under root, in document order, including those inside open shadow roots
+// (and the shadow root of root itself, when it's a shadow host).
+function deepElements(root) {
+    var found = [];
+    function walk(node) {
+        Array.from(node.querySelectorAll('*')).forEach(function(el) {
+            found.push(el);
+            if (el.shadowRoot) {
+                walk(el.shadowRoot);
+            }
+        });
+    }
+    walk(root);
+    if (root.shadowRoot) {
+        walk(root.shadowRoot);
+    }
+    return found;
+}
+
+function inShadowRoot(el) {
+    return el.getRootNode() instanceof ShadowRoot;
+}
+
+function ownText(el) {
+    return Array.from(el.childNodes)
+        .filter(function(node) { return node.nodeType === Node.TEXT_NODE; })
+        .map(function(node) { return node.textContent; });
+}
+"#;
+
+/// Finds the displayed elements inside shadow roots under the element passed as the first
+/// argument (or the document if it's null). The second argument says how to match them:
+/// `"text"` or `"partial-text"` match a text node of the element against the fourth argument,
+/// `"attribute"` or `"partial-attribute"` match the attribute named by the third argument.
+/// Requires `DOM_FUNCTIONS`.
+pub const FIND_IN_SHADOW_ROOTS: &str = r#"
+var root = arguments[0] || document;
+var mode = arguments[1];
+var attribute = arguments[2];
+var value = arguments[3];
+function matches(text) {
+    return text !== null && (mode.indexOf('partial') === 0 ? text.indexOf(value) !== -1 : text === value);
+}
+return deepElements(root).filter(function(el) {
+    if (!inShadowRoot(el) || !isDisplayed(el)) {
+        return false;
+    }
+    if (mode === 'text' || mode === 'partial-text') {
+        return ownText(el).some(matches);
+    }
+    return matches(el.getAttribute(attribute));
+});
+"#;
+
+/// Finds the innermost displayed elements under the element passed as the first argument (or
+/// the document if it's null) whose text, with whitespace normalized, equals the second argument
+/// ignoring case. Lowercased in javascript, so letters outside ASCII are folded too.
+/// Requires `DOM_FUNCTIONS`.
+pub const FIND_TEXT_IGNORING_CASE: &str = r#"
+var root = arguments[0] || document;
+var value = normalize(arguments[1]).toLowerCase();
+var matching = Array.from(root.querySelectorAll('*')).filter(function(el) {
+    return normalize(el.textContent).toLowerCase() === value;
+});
+return matching.filter(function(el) {
+    return isDisplayed(el) && !matching.some(function(other) {
+        return other !== el && el.contains(other);
+    });
+});
+"#;
+
+/// Finds the parent of the element passed as the first argument, stepping out of a
+/// shadow root to its host. Returns null at the top of the document.
+pub const PARENT_ELEMENT: &str = r#"
+var el = arguments[0];
+var rootNode = el.getRootNode();
+return el.parentElement || (rootNode instanceof ShadowRoot ? rootNode.host : null);
+"#;
+
+/// Functions for working out elements' ARIA roles and accessible names, roughly like assistive
+/// technology does: an explicit `role` attribute, or the role implied by the tag, and the name from
+/// `aria-labelledby`, `aria-label`, a `<label>`, alt text, the element's text, or its title.
+/// Requires `DOM_FUNCTIONS`.
+pub const ROLE_FUNCTIONS: &str = r#"
+function implicitRole(el) {
+    var tag = el.tagName.toLowerCase();
+    var type = (el.getAttribute('type') || '').toLowerCase();
+    switch (tag) {
+        case 'button':
+            return 'button';
+        case 'a':
+        case 'area':
+            return el.hasAttribute('href') ? 'link' : null;
+        case 'textarea':
+            return 'textbox';
+        case 'select':
+            return el.multiple || el.size > 1 ? 'listbox' : 'combobox';
+        case 'option':
+            return 'option';
+        case 'img':
+            return el.getAttribute('alt') === '' ? null : 'img';
+        case 'h1': case 'h2': case 'h3': case 'h4': case 'h5': case 'h6':
+            return 'heading';
+        case 'input':
+            switch (type) {
+                case 'button': case 'submit': case 'reset': case 'image':
+                    return 'button';
+                case 'checkbox':
+                    return 'checkbox';
+                case 'radio':
+                    return 'radio';
+                case 'range':
+                    return 'slider';
+                case 'search':
+                    return el.hasAttribute('list') ? 'combobox' : 'searchbox';
+                case '': case 'text': case 'email': case 'tel': case 'url':
+                    return el.hasAttribute('list') ? 'combobox' : 'textbox';
+            }
+    }
+    return null;
+}
+
+function roleOf(el) {
+    var explicit = normalize(el.getAttribute('role')).split(' ')[0];
+    return explicit || implicitRole(el);
+}
+
+function accessibleName(el) {
+    var labelledBy = normalize(el.getAttribute('aria-labelledby'));
+    if (labelledBy) {
+        var referenced = labelledBy.split(' ')
+            .map(function(id) { return el.getRootNode().getElementById(id); })
+            .filter(Boolean)
+            .map(function(label) { return label.textContent; })
+            .join(' ');
+        if (normalize(referenced)) {
+            return normalize(referenced);
+        }
+    }
+
+    if (normalize(el.getAttribute('aria-label'))) {
+        return normalize(el.getAttribute('aria-label'));
+    }
+
+    if (el.labels && el.labels.length) {
+        return normalize(Array.from(el.labels).map(function(label) { return label.textContent; }).join(' '));
+    }
+
+    var tag = el.tagName.toLowerCase();
+    var type = (el.getAttribute('type') || '').toLowerCase();
+    if (tag === 'input' && ['button', 'submit', 'reset'].indexOf(type) !== -1) {
+        return normalize(el.value) || { submit: 'Submit', reset: 'Reset' }[type] || '';
+    }
+    if (tag === 'img' || (tag === 'input' && type === 'image')) {
+        return normalize(el.getAttribute('alt'));
+    }
+    if (['input', 'textarea', 'select'].indexOf(tag) === -1 && normalize(el.textContent)) {
+        return normalize(el.textContent);
+    }
+    return normalize(el.getAttribute('title') || el.getAttribute('placeholder'));
+}
+"#;
+
+/// Finds the displayed elements under the element passed as the first argument (or the document
+/// if it's null) with the ARIA role in the second argument and the accessible name in the third.
+/// Elements whose name matches exactly come back if there are any, otherwise those whose name
+/// contains it. Searches inside open shadow roots too. Requires `DOM_FUNCTIONS` and `ROLE_FUNCTIONS`.
+pub const FIND_BY_ROLE: &str = r#"
+var root = arguments[0] || document;
+var role = arguments[1];
+var name = normalize(arguments[2]);
+var withRole = deepElements(root).filter(function(el) {
+    return roleOf(el) === role && isDisplayed(el);
+});
+var exact = withRole.filter(function(el) { return accessibleName(el) === name; });
+if (exact.length) {
+    return exact;
+}
+return withRole.filter(function(el) { return accessibleName(el).indexOf(name) !== -1; });
+"#;
+
+/// Collects the text a locator might have been meant to match from the displayed elements inside
+/// the element passed as the first argument (or the whole page when it's null), as `[text, kind]`
+/// pairs: accessible names (kind is the role), the text of elements without child elements
+/// (kind is the tag), placeholders, and ids. Requires `DOM_FUNCTIONS` and `ROLE_FUNCTIONS`.
+pub const LOCATOR_CANDIDATES: &str = r#"
+var seen = {};
+var candidates = [];
+function add(text, kind) {
+    text = normalize(text);
+    if (text && text.length <= 100 && !seen[kind + ':' + text]) {
+        seen[kind + ':' + text] = true;
+        candidates.push([text, kind]);
+    }
+}
+deepElements(arguments[0] || document.body).forEach(function(el) {
+    if (!isDisplayed(el)) {
+        return;
+    }
+    var role = roleOf(el);
+    if (role) {
+        add(accessibleName(el), role);
+    } else if (el.children.length === 0) {
+        add(el.textContent, el.tagName.toLowerCase());
+    }
+    add(el.getAttribute('placeholder'), 'placeholder');
+    add(el.id, 'id');
+});
+return candidates;
+"#;
+
+/// Counts the displayed elements in the array passed as the first argument.
+/// Requires `DOM_FUNCTIONS`.
+pub const COUNT_DISPLAYED: &str = "return Array.from(arguments[0]).filter(isDisplayed).length;";
+
+/// Gets the on screen position and size of each element in the array passed as the first argument.
+pub const ELEMENT_RECTS: &str = r#"
+return Array.from(arguments[0]).map(function(el) {
+    var rect = el.getBoundingClientRect();
+    return { x: rect.left, y: rect.top, width: rect.width, height: rect.height };
+});
+"#;
+
+/// Describes an element for the self-healing locator cache (see `healing::Fingerprint`).
+/// Requires `DOM_FUNCTIONS`.
+pub const FINGERPRINT_FUNCTIONS: &str = r#"
+function pathOf(el) {
+    var segments = [];
+    while (el) {
+        var tag = el.tagName.toLowerCase();
+        var parent = el.parentElement;
+        var rootNode = el.getRootNode();
+        var siblings = parent
+            ? Array.from(parent.children)
+            : (rootNode instanceof ShadowRoot ? Array.from(rootNode.children) : [el]);
+        var sameTag = siblings.filter(function(sibling) { return sibling.tagName === el.tagName; });
+        segments.unshift(sameTag.length > 1 ? tag + '[' + (sameTag.indexOf(el) + 1) + ']' : tag);
+        el = parent || (rootNode instanceof ShadowRoot ? rootNode.host : null);
+    }
+    return segments.join('/');
+}
+
+function fingerprintOf(el) {
+    var attributes = {};
+    Array.from(el.attributes).forEach(function(attr) {
+        if (['id', 'class', 'style'].indexOf(attr.name) === -1) {
+            attributes[attr.name] = attr.value;
+        }
+    });
+    return {
+        tag: el.tagName.toLowerCase(),
+        id: el.id || '',
+        classes: Array.from(el.classList),
+        text: normalize(el.textContent).slice(0, 200),
+        path: pathOf(el),
+        attributes: attributes
+    };
+}
+"#;
+
+/// Gets the fingerprint of the element passed as the first argument.
+/// Requires `DOM_FUNCTIONS` and `FINGERPRINT_FUNCTIONS`.
+pub const FINGERPRINT: &str = "return fingerprintOf(arguments[0]);";
+
+/// Gets every displayed element on the page, and their fingerprints, as
+/// `{ elements: [...], fingerprints: [...] }`. Requires `DOM_FUNCTIONS` and `FINGERPRINT_FUNCTIONS`.
+pub const FINGERPRINT_CANDIDATES: &str = r#"
+var elements = deepElements(document.body).filter(isDisplayed);
+return { elements: elements, fingerprints: elements.map(fingerprintOf) };
+"#;
+
+/// Finds the form control labelled by the element passed as the first argument, for swapping a
+/// located label for the control it labels. Returns `{ control, description, how }`, or null if
+/// the element isn't a label for anything. Controls include inputs, textareas, selects,
+/// contenteditable editors and elements with a textbox or combobox role. A control is found by,
+/// in order: `aria-labelledby` or `aria-describedby` pointing at the element, the element
+/// containing it (for `label` and `span` elements), a `for` attribute, a following sibling, or
+/// a search from up to 5 ancestors. When the second argument is true, `div` and `p` elements
+/// count as labels too.
+pub const FIND_LABELLED_CONTROL: &str = r#"
+var CONTROLS = 'input:not([type="hidden"]), textarea, select, [contenteditable=""], '
+    + '[contenteditable="true"], [role="textbox"], [role="combobox"]';
+var LABEL_TAGS = arguments[1] ? ['label', 'span', 'div', 'p'] : ['label', 'span'];
+var el = arguments[0];
+
+function describe(control) {
+    var description = control.tagName.toLowerCase();
+    if (control.id) {
+        description += '#' + control.id;
+    } else if (control.getAttribute('name')) {
+        description += '[name="' + control.getAttribute('name') + '"]';
+    }
+    if (control.getAttribute('role')) {
+        description += '[role="' + control.getAttribute('role') + '"]';
+    }
+    return description;
+}
+
+function found(control, how) {
+    return control ? { control: control, description: describe(control), how: how } : null;
+}
+
+function firstControl(root) {
+    return root.matches(CONTROLS) ? root : root.querySelector(CONTROLS);
+}
+
+if (el.matches(CONTROLS)) {
+    return null;
+}
+
+var rootNode = el.getRootNode();
+if (el.id) {
+    var referencing = Array.from(rootNode.querySelectorAll('[aria-labelledby], [aria-describedby]'))
+        .filter(function(control) { return control.matches(CONTROLS); });
+    var attributes = ['aria-labelledby', 'aria-describedby'];
+    for (var i = 0; i < attributes.length; i++) {
+        var control = referencing.find(function(control) {
+            return (control.getAttribute(attributes[i]) || '').split(/\s+/).indexOf(el.id) !== -1;
+        });
+        if (control) {
+            return found(control, attributes[i]);
+        }
+    }
+}
+
+if (LABEL_TAGS.indexOf(el.tagName.toLowerCase()) === -1) {
+    return null;
+}
+
+var contained = el.querySelector(CONTROLS);
+if (contained) {
+    return found(contained, 'the label contains it');
+}
+
+var forAttr = el.getAttribute('for');
+if (forAttr) {
+    var target = rootNode.getElementById(forAttr) || rootNode.querySelector('[name="' + CSS.escape(forAttr) + '"]');
+    if (target) {
+        return found(target, 'for attribute');
+    }
+}
+
+for (var sibling = el.nextElementSibling; sibling; sibling = sibling.nextElementSibling) {
+    var siblingControl = firstControl(sibling);
+    if (siblingControl) {
+        return found(siblingControl, 'it follows the label');
+    }
+}
+
+var ancestor = el;
+for (var depth = 0; depth < 5 && ancestor.parentElement; depth++) {
+    ancestor = ancestor.parentElement;
+    var nearby = ancestor.querySelector(CONTROLS);
+    if (nearby) {
+        return found(nearby, 'it is near the label');
+    }
+}
+return null;
+"#;
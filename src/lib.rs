@@ -28,11 +28,16 @@
 //! To get started, check out the [narrative documentation](https://bcpeinhardt.github.io/schnauzerUI/)
 
 pub mod datatable;
+pub mod error;
+pub mod healing;
 pub mod interpreter;
+pub mod locator;
 pub mod parser;
 pub mod scanner;
+pub mod settings;
 pub mod test_report;
 pub mod webdriver;
 
+mod downloads;
 mod environment;
 mod js;
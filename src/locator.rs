@@ -0,0 +1,975 @@
+//! This module contains the strategies the `locate` command tries, in order, to turn a
+//! locator into a web element.
+//!
+//! Each strategy implements `LocatorStrategy`. The interpreter runs them in the order
+//! held by `Locators`, and the first strategy to find a displayed element wins. The order
+//! can be changed from a script with `set locator-order`, and library users can register
+//! their own strategies. A locator can also skip the heuristics and name its strategy
+//! with a prefix, like `"id:submit"` or `"css:.btn-primary"` (see `PREFIXES`).
+//!
+//! ```no_run
+//! use anyhow::Result;
+//! use async_trait::async_trait;
+//! use schnauzer_ui::locator::{xpath_literal, AttributeStrategy, LocatorStrategy, SearchRoot};
+//! use schnauzer_ui::settings::Settings;
+//! use thirtyfour::WebElement;
+//!
+//! // Matching a test id attribute is common enough to have a built in strategy.
+//! let mut settings = Settings::default();
+//! settings.locators.register_first(AttributeStrategy::new("data-qa", "data-qa"));
+//!
+//! // Anything else can implement `LocatorStrategy`.
+//! struct ValueStrategy;
+//!
+//! #[async_trait]
+//! impl LocatorStrategy for ValueStrategy {
+//!     fn name(&self) -> &str {
+//!         "value"
+//!     }
+//!
+//!     async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+//!         root.displayed(root.xpath(&format!("//input[@value={}]", xpath_literal(locator))))
+//!             .await
+//!     }
+//! }
+//!
+//! settings.locators.register(ValueStrategy);
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use thirtyfour::{extensions::query::ElementQuery, prelude::*, session::handle::SessionHandle};
+
+use crate::js::{
+    DOM_FUNCTIONS, FIND_BY_ROLE, FIND_IN_SHADOW_ROOTS, FIND_TEXT_IGNORING_CASE, ROLE_FUNCTIONS,
+};
+
+/// The ARIA roles `locate` can match by, like `locate button "Submit"`.
+/// Each one is a strategy of its own, which is only used for locators with that role.
+pub const ROLES: [&str; 13] = [
+    "button",
+    "link",
+    "textbox",
+    "searchbox",
+    "checkbox",
+    "radio",
+    "switch",
+    "combobox",
+    "listbox",
+    "option",
+    "heading",
+    "tab",
+    "img",
+];
+
+/// Quote text for use in an XPath expression. XPath string literals can't escape quotes,
+/// so text containing both kinds of quote is stitched together with `concat()`.
+pub fn xpath_literal(text: &str) -> String {
+    if !text.contains('\'') {
+        format!("'{}'", text)
+    } else if !text.contains('"') {
+        format!("\"{}\"", text)
+    } else {
+        format!(
+            "concat('{}')",
+            text.split('\'').collect::<Vec<_>>().join("', \"'\", '")
+        )
+    }
+}
+
+/// Text on the page that a locator might have been meant to match, for suggesting
+/// alternatives when a locate fails.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Candidate {
+    /// The text, like an element's accessible name, its text, or its placeholder
+    pub text: String,
+
+    /// What the text belongs to, like a role (`"button"`), a tag (`"span"`) or `"placeholder"`
+    pub kind: String,
+}
+
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' ({})", self.text, self.kind)
+    }
+}
+
+/// Pick up to `limit` candidates closest to a locator which failed to match, best first.
+/// Closeness is the edit distance between the lowercased texts with spaces removed,
+/// relative to the longer text, so `"Log In"` is close to `"Login"`. Candidates containing
+/// the locator (or contained in it) count as close too. Candidates which aren't close are left out.
+pub fn closest_matches<'a>(
+    locator: &str,
+    candidates: &'a [Candidate],
+    limit: usize,
+) -> Vec<&'a Candidate> {
+    fn compact(text: &str) -> Vec<char> {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+
+    let target = compact(locator);
+    if target.is_empty() {
+        return vec![];
+    }
+
+    let mut scored = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let text = compact(&candidate.text);
+            if text == target {
+                // An exact match would have been found, so this is some other kind of miss
+                return Some((0.0, candidate));
+            }
+            let mut score =
+                edit_distance(&target, &text) as f64 / target.len().max(text.len()) as f64;
+            let contains = |outer: &[char], inner: &[char]| {
+                inner.len() <= outer.len() && outer.windows(inner.len()).any(|w| w == inner)
+            };
+            if contains(&text, &target) || contains(&target, &text) {
+                score = score.min(0.4);
+            }
+            (score <= 0.5).then_some((score, candidate))
+        })
+        .collect::<Vec<_>>();
+
+    scored.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    scored
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .take(limit)
+        .collect()
+}
+
+/// The Levenshtein distance between two strings of characters: how many single character
+/// insertions, deletions or substitutions it takes to turn one into the other.
+pub(crate) fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(ca != cb);
+            row.push(substitution.min(prev_row[j + 1] + 1).min(row[j] + 1));
+        }
+        prev_row = row;
+    }
+    prev_row[b.len()]
+}
+
+/// Where an element must be on screen relative to another, like `locate "Edit" right-of "Jane Doe"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// Anywhere, closest first
+    Near,
+
+    /// Starting at or past the anchor's right edge
+    RightOf,
+
+    /// Ending at or before the anchor's left edge
+    LeftOf,
+
+    /// Ending at or before the anchor's top edge
+    Above,
+
+    /// Starting at or past the anchor's bottom edge
+    Below,
+}
+
+impl std::fmt::Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Relation::Near => "near",
+            Relation::RightOf => "right-of",
+            Relation::LeftOf => "left-of",
+            Relation::Above => "above",
+            Relation::Below => "below",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An element's position and size on screen, in CSS pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Rect {
+    /// Distance of the left edge from the left of the viewport
+    pub x: f64,
+
+    /// Distance of the top edge from the top of the viewport
+    pub y: f64,
+
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    /// The distance between the centers of two rects.
+    fn distance(&self, other: &Rect) -> f64 {
+        let ((x1, y1), (x2, y2)) = (self.center(), other.center());
+        (x1 - x2).hypot(y1 - y2)
+    }
+}
+
+/// Rank candidate rects by where they are relative to an anchor rect. Returns the positions
+/// (in `candidates`) of those in the right direction, closest first.
+pub fn rank_by_relation(relation: Relation, anchor: &Rect, candidates: &[Rect]) -> Vec<usize> {
+    // Allow for rounding, so elements right next to each other count
+    const TOLERANCE: f64 = 1.0;
+
+    let mut ranked = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, rect)| match relation {
+            Relation::Near => true,
+            Relation::RightOf => rect.x >= anchor.right() - TOLERANCE,
+            Relation::LeftOf => rect.right() <= anchor.x + TOLERANCE,
+            Relation::Above => rect.bottom() <= anchor.y + TOLERANCE,
+            Relation::Below => rect.y >= anchor.bottom() - TOLERANCE,
+        })
+        .map(|(i, rect)| (i, rect.distance(anchor)))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    ranked.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Where a strategy should search for elements.
+#[derive(Debug, Clone)]
+pub enum SearchRoot {
+    /// Search the whole page.
+    Document(SessionHandle),
+
+    /// Search the descendants of an element (used by `under`).
+    Element(WebElement),
+}
+
+impl SearchRoot {
+    /// Adapt an XPath written from the top of the document (`//...`) to this root.
+    /// Under an element, the path is made relative so it only matches descendants.
+    pub fn xpath(&self, path: &str) -> By {
+        match self {
+            SearchRoot::Document(_) => By::XPath(path),
+            SearchRoot::Element(_) => By::XPath(&format!(".{}", path)),
+        }
+    }
+
+    /// Start a thirtyfour query from this root.
+    pub fn query(&self, by: By) -> ElementQuery {
+        match self {
+            SearchRoot::Document(handle) => handle.query(by),
+            SearchRoot::Element(elem) => elem.query(by),
+        }
+    }
+
+    /// All displayed elements under this root matching the selector, without waiting.
+    pub async fn displayed(&self, by: By) -> Result<Vec<WebElement>> {
+        Ok(self.query(by).and_displayed().nowait().all().await?)
+    }
+
+    /// The browser session, for searching outside the root.
+    pub fn handle(&self) -> &SessionHandle {
+        match self {
+            SearchRoot::Document(handle) => handle,
+            SearchRoot::Element(elem) => &elem.handle,
+        }
+    }
+
+    /// All elements under this root matching the selector, displayed or not, without waiting.
+    pub async fn all(&self, by: By) -> Result<Vec<WebElement>> {
+        Ok(self.query(by).nowait().all().await?)
+    }
+
+    /// Displayed elements inside the open shadow roots under this root (including the root's
+    /// own shadow root, if it's a shadow host), which XPath and CSS queries can't see into.
+    pub async fn in_shadow_roots(&self, query: ShadowQuery<'_>) -> Result<Vec<WebElement>> {
+        let (mode, attribute, value) = match query {
+            ShadowQuery::Text(text) => ("text", "", text),
+            ShadowQuery::PartialText(text) => ("partial-text", "", text),
+            ShadowQuery::Attribute(attribute, value) => ("attribute", attribute, value),
+            ShadowQuery::PartialAttribute(attribute, value) => {
+                ("partial-attribute", attribute, value)
+            }
+        };
+        Ok(self
+            .handle()
+            .execute(
+                &format!("{}{}", DOM_FUNCTIONS, FIND_IN_SHADOW_ROOTS),
+                vec![self.to_json()?, mode.into(), attribute.into(), value.into()],
+            )
+            .await?
+            .elements()?)
+    }
+
+    /// The root as a script argument. Scripts search the document when it's null.
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        Ok(match self {
+            SearchRoot::Document(_) => serde_json::Value::Null,
+            SearchRoot::Element(elem) => elem.to_json()?,
+        })
+    }
+}
+
+/// How to match elements inside shadow roots (see `SearchRoot::in_shadow_roots`).
+#[derive(Debug, Clone, Copy)]
+pub enum ShadowQuery<'a> {
+    /// Elements with a text node equal to the text
+    Text(&'a str),
+
+    /// Elements with a text node containing the text
+    PartialText(&'a str),
+
+    /// Elements whose attribute (the first field) equals the value
+    Attribute(&'a str, &'a str),
+
+    /// Elements whose attribute (the first field) contains the value
+    PartialAttribute(&'a str, &'a str),
+}
+
+/// One way of matching a locator to elements on the page.
+#[async_trait]
+pub trait LocatorStrategy: Send + Sync {
+    /// A short name for the strategy, used by `set locator-order`.
+    fn name(&self) -> &str;
+
+    /// Find the elements under `root` matching `locator`, best match first.
+    /// An empty Vec means the strategy doesn't apply and the next one should be tried.
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>>;
+
+    /// Whether the strategy forgives differences in case or whitespace. Loose strategies
+    /// are skipped when text matching is strict (see `Locators::set_strict`).
+    fn is_loose(&self) -> bool {
+        false
+    }
+}
+
+/// The elements a strategy found for a locator.
+#[derive(Debug, Clone)]
+pub struct LocatorMatch {
+    /// The name of the strategy which matched
+    pub strategy: String,
+
+    /// The matching elements, best match first. Never empty.
+    pub elements: Vec<WebElement>,
+}
+
+/// The built in strategies a locator can name with a prefix, like `"id:submit"`. Other text
+/// before a colon (like in `"Note: required"`) is part of the locator.
+pub const PREFIXES: [&str; 6] = ["id", "css", "xpath", "text", "testid", "label"];
+
+/// The strategies the `locate` command knows about.
+///
+/// A locator with a prefix naming a strategy, like `"id:submit"` or `"css:.btn-primary"`,
+/// uses only that strategy. Any other locator tries the strategies in the heuristic order,
+/// one after another.
+#[derive(Clone)]
+pub struct Locators {
+    /// Every known strategy
+    strategies: Vec<Arc<dyn LocatorStrategy>>,
+
+    /// The names of the strategies tried, in order, for a locator without a prefix
+    order: Vec<String>,
+
+    /// Whether to skip loose strategies for a locator without a prefix
+    strict: bool,
+
+    /// The names of the strategies a locator can start with, like `"id:"`
+    prefixes: Vec<String>,
+}
+
+impl std::fmt::Debug for Locators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl Default for Locators {
+    /// The built in strategies, in Schnauzer UI's default precedence.
+    fn default() -> Self {
+        let mut locators = Self {
+            strategies: vec![],
+            order: vec![],
+            strict: false,
+            prefixes: vec![],
+        };
+        locators
+            .register(PlaceholderStrategy)
+            .register(TextStrategy)
+            .register(PartialTextStrategy)
+            .register(AttributeStrategy::new("title", "title"))
+            .register(AttributeStrategy::new("aria-label", "aria-label").in_shadow_roots())
+            .register(AttributeStrategy::new("id", "id").in_shadow_roots())
+            .register(NameStrategy)
+            .register(ClassStrategy)
+            .register(TagStrategy)
+            .register(XPathStrategy)
+            .register(NormalizedTextStrategy)
+            .register(CaseInsensitiveTextStrategy)
+            .register(ContainsStrategy)
+            .register_prefix_only(CssStrategy)
+            .register_prefix_only(TestIdStrategy)
+            .register_prefix_only(LabelStrategy);
+        for role in ROLES {
+            locators.register_prefix_only(RoleStrategy::new(role));
+        }
+
+        // Only the documented prefixes, so text with a colon in it isn't mistaken for one.
+        // Strategies registered later can always be named with a prefix.
+        locators.prefixes = PREFIXES.iter().map(|prefix| prefix.to_string()).collect();
+        locators
+    }
+}
+
+impl Locators {
+    /// The names of the strategies tried for a locator without a prefix, in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.order.iter().map(|name| name.as_str()).collect()
+    }
+
+    /// Get a strategy by name.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn LocatorStrategy>> {
+        self.strategies.iter().find(|s| s.name() == name)
+    }
+
+    /// Add a strategy to be tried after all the others.
+    pub fn register(&mut self, strategy: impl LocatorStrategy + 'static) -> &mut Self {
+        self.order.push(strategy.name().to_owned());
+        self.register_prefix_only(strategy)
+    }
+
+    /// Add a strategy to be tried before all the others.
+    pub fn register_first(&mut self, strategy: impl LocatorStrategy + 'static) -> &mut Self {
+        self.order.insert(0, strategy.name().to_owned());
+        self.register_prefix_only(strategy)
+    }
+
+    /// Add a strategy which is only used when a locator asks for it with a prefix.
+    /// It can still be added to the heuristic order with `set_order`.
+    pub fn register_prefix_only(&mut self, strategy: impl LocatorStrategy + 'static) -> &mut Self {
+        // A strategy registered again under the same name replaces the old one
+        self.strategies.retain(|s| s.name() != strategy.name());
+        if !self.prefixes.iter().any(|prefix| prefix == strategy.name()) {
+            self.prefixes.push(strategy.name().to_owned());
+        }
+        self.strategies.push(Arc::new(strategy));
+        self
+    }
+
+    /// Only try the named strategies, in the given order, for a locator without a prefix.
+    pub fn set_order(&mut self, names: &[&str]) -> Result<()> {
+        for name in names {
+            if self.get(name).is_none() {
+                bail!(
+                    "Unknown locator strategy \"{}\". The strategies are: {}",
+                    name,
+                    self.strategies
+                        .iter()
+                        .map(|s| s.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
+        }
+        self.order = names.iter().map(|name| name.to_string()).collect();
+        Ok(())
+    }
+
+    /// If the locator starts with a prefix and a colon, like `"id:submit"`, get that strategy
+    /// and the rest of the locator. A backslash at the start escapes the prefix, so `"\id:submit"`
+    /// is plain text.
+    pub fn explicit_strategy<'a>(
+        &self,
+        locator: &'a str,
+    ) -> Option<(&Arc<dyn LocatorStrategy>, &'a str)> {
+        let (prefix, rest) = locator.split_once(':')?;
+        if !self.prefixes.iter().any(|name| name == prefix) {
+            return None;
+        }
+        self.get(prefix).map(|strategy| (strategy, rest))
+    }
+
+    /// The locator without the backslash escaping its prefix, if it has one.
+    fn unescape(locator: &str) -> &str {
+        locator.strip_prefix('\\').unwrap_or(locator)
+    }
+
+    /// Only match text exactly as written, skipping loose strategies (like `normalized-text`)
+    /// unless a locator asks for one with a prefix.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// The strategies tried for a locator without a prefix, in order.
+    fn heuristic_strategies(&self) -> impl Iterator<Item = &Arc<dyn LocatorStrategy>> {
+        self.order
+            .iter()
+            .filter_map(|name| self.get(name))
+            .filter(|strategy| !(self.strict && strategy.is_loose()))
+    }
+
+    /// The names of the strategies `find` tries for a locator, in order.
+    pub fn strategies_for(&self, locator: &str) -> Vec<&str> {
+        match self.explicit_strategy(locator) {
+            Some((strategy, _)) => vec![strategy.name()],
+            None => self.heuristic_strategies().map(|s| s.name()).collect(),
+        }
+    }
+
+    /// Find the elements matching a locator. A locator with a strategy prefix uses
+    /// just that strategy, otherwise each strategy is tried in order and the elements
+    /// from the first one which matches are returned.
+    /// A strategy which errors (like an xpath strategy given plain text) is skipped.
+    pub async fn find(&self, root: &SearchRoot, locator: &str) -> Option<LocatorMatch> {
+        if let Some((strategy, rest)) = self.explicit_strategy(locator) {
+            return Self::try_strategy(strategy, root, rest).await;
+        }
+
+        let locator = Self::unescape(locator);
+        for strategy in self.heuristic_strategies() {
+            if let Some(found) = Self::try_strategy(strategy, root, locator).await {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// Find the elements matching a locator with just the named strategy, like the one
+    /// for a locator's role.
+    pub async fn find_with(
+        &self,
+        name: &str,
+        root: &SearchRoot,
+        locator: &str,
+    ) -> Option<LocatorMatch> {
+        Self::try_strategy(self.get(name)?, root, locator).await
+    }
+
+    async fn try_strategy(
+        strategy: &Arc<dyn LocatorStrategy>,
+        root: &SearchRoot,
+        locator: &str,
+    ) -> Option<LocatorMatch> {
+        match strategy.find(root, locator).await {
+            Ok(elements) if !elements.is_empty() => Some(LocatorMatch {
+                strategy: strategy.name().to_owned(),
+                elements,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Matches an input by its placeholder, or failing that, part of its placeholder.
+/// Also looks for the exact placeholder inside shadow roots.
+pub struct PlaceholderStrategy;
+
+#[async_trait]
+impl LocatorStrategy for PlaceholderStrategy {
+    fn name(&self) -> &str {
+        "placeholder"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let exact = root
+            .displayed(root.xpath(&format!("//input[@placeholder={}]", xpath_literal(locator))))
+            .await?;
+        if !exact.is_empty() {
+            return Ok(exact);
+        }
+        let exact = root
+            .in_shadow_roots(ShadowQuery::Attribute("placeholder", locator))
+            .await?;
+        if !exact.is_empty() {
+            return Ok(exact);
+        }
+
+        root.displayed(root.xpath(&format!(
+            "//input[contains(@placeholder, {})]",
+            xpath_literal(locator)
+        )))
+        .await
+    }
+}
+
+/// Matches an element by its text, including inside shadow roots.
+pub struct TextStrategy;
+
+#[async_trait]
+impl LocatorStrategy for TextStrategy {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let found = root
+            .displayed(root.xpath(&format!("//*[text()={}]", xpath_literal(locator))))
+            .await?;
+        if !found.is_empty() {
+            return Ok(found);
+        }
+        root.in_shadow_roots(ShadowQuery::Text(locator)).await
+    }
+}
+
+/// Matches an element by part of its text.
+pub struct PartialTextStrategy;
+
+#[async_trait]
+impl LocatorStrategy for PartialTextStrategy {
+    fn name(&self) -> &str {
+        "partial-text"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.displayed(root.xpath(&format!(
+            "//*[contains(text(), {})]",
+            xpath_literal(locator)
+        )))
+        .await
+    }
+}
+
+/// Matches an element whose attribute equals the locator.
+/// Used for the built in title, aria-label and id strategies, and handy for project specific test ids.
+pub struct AttributeStrategy {
+    name: String,
+    attribute: String,
+    shadow: bool,
+}
+
+impl AttributeStrategy {
+    /// Create a strategy with the given name, matching the given attribute.
+    pub fn new(name: impl Into<String>, attribute: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            attribute: attribute.into(),
+            shadow: false,
+        }
+    }
+
+    /// Also look inside shadow roots when nothing outside them matches. This costs a
+    /// JavaScript call on every look that finds nothing.
+    pub fn in_shadow_roots(mut self) -> Self {
+        self.shadow = true;
+        self
+    }
+}
+
+#[async_trait]
+impl LocatorStrategy for AttributeStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let found = root
+            .displayed(root.xpath(&format!(
+                "//*[@{}={}]",
+                self.attribute,
+                xpath_literal(locator)
+            )))
+            .await?;
+        if !found.is_empty() || !self.shadow {
+            return Ok(found);
+        }
+        root.in_shadow_roots(ShadowQuery::Attribute(&self.attribute, locator))
+            .await
+    }
+}
+
+/// Matches an element by its name attribute.
+pub struct NameStrategy;
+
+#[async_trait]
+impl LocatorStrategy for NameStrategy {
+    fn name(&self) -> &str {
+        "name"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.displayed(By::Name(locator)).await
+    }
+}
+
+/// Matches an element by one of its classes.
+pub struct ClassStrategy;
+
+#[async_trait]
+impl LocatorStrategy for ClassStrategy {
+    fn name(&self) -> &str {
+        "class"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.displayed(By::ClassName(locator)).await
+    }
+}
+
+/// Matches an element by its tag name.
+pub struct TagStrategy;
+
+#[async_trait]
+impl LocatorStrategy for TagStrategy {
+    fn name(&self) -> &str {
+        "tag"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.displayed(By::Tag(locator)).await
+    }
+}
+
+/// Treats the locator as an XPath. Unlike the other strategies, this one
+/// also matches hidden elements, like the file input behind an upload button.
+pub struct XPathStrategy;
+
+#[async_trait]
+impl LocatorStrategy for XPathStrategy {
+    fn name(&self) -> &str {
+        "xpath"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.all(root.xpath(locator)).await
+    }
+}
+
+/// Matches elements whose text, including the text of their children, is the locator once
+/// runs of whitespace (including non-breaking spaces and line breaks) are collapsed, so
+/// `"Sign in"` matches `<button><b>Sign</b>\n in</button>`. Prefers the innermost element.
+pub struct NormalizedTextStrategy;
+
+#[async_trait]
+impl LocatorStrategy for NormalizedTextStrategy {
+    fn name(&self) -> &str {
+        "normalized-text"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let text = normalized_text(".");
+        let locator = xpath_literal(&normalize_whitespace(locator));
+        root.displayed(root.xpath(&format!(
+            "//*[{text} = {locator} and not(.//*[{text} = {locator}])]"
+        )))
+        .await
+    }
+
+    fn is_loose(&self) -> bool {
+        true
+    }
+}
+
+/// Like `NormalizedTextStrategy`, but also ignores the case of letters, so `"sign in"`
+/// matches `<button>Sign In</button>`.
+pub struct CaseInsensitiveTextStrategy;
+
+#[async_trait]
+impl LocatorStrategy for CaseInsensitiveTextStrategy {
+    fn name(&self) -> &str {
+        "case-insensitive-text"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        // XPath 1.0 can only lowercase letters it's given, so both sides are lowercased in javascript
+        Ok(root
+            .handle()
+            .execute(
+                &format!("{}{}", DOM_FUNCTIONS, FIND_TEXT_IGNORING_CASE),
+                vec![root.to_json()?, locator.into()],
+            )
+            .await?
+            .elements()?)
+    }
+
+    fn is_loose(&self) -> bool {
+        true
+    }
+}
+
+/// An XPath expression for the text of a node and its children, with non-breaking spaces
+/// turned into spaces and runs of whitespace collapsed.
+fn normalized_text(node: &str) -> String {
+    format!(
+        "normalize-space(translate(string({}), '\u{a0}', ' '))",
+        node
+    )
+}
+
+/// Collapse runs of whitespace (including non-breaking spaces) into single spaces and trim the ends.
+fn normalize_whitespace(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The last resort. Matches the innermost elements with the locator anywhere in their contents.
+pub struct ContainsStrategy;
+
+#[async_trait]
+impl LocatorStrategy for ContainsStrategy {
+    fn name(&self) -> &str {
+        "contains"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        // Only the innermost elements containing the text, so each occurrence is one match.
+        // The last occurrence on the page comes first, as it always has.
+        let text = xpath_literal(locator);
+        let mut containing = root
+            .displayed(root.xpath(&format!(
+                "//*[contains(., {text}) and not(.//*[contains(., {text})])]"
+            )))
+            .await?;
+        containing.reverse();
+        Ok(containing)
+    }
+}
+
+/// Treats the locator as a CSS selector. Only used with the `css:` prefix.
+/// Like the xpath strategy, it also matches hidden elements.
+pub struct CssStrategy;
+
+#[async_trait]
+impl LocatorStrategy for CssStrategy {
+    fn name(&self) -> &str {
+        "css"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        root.all(By::Css(locator)).await
+    }
+}
+
+/// Matches an element by any of the common test id attributes. Only used with the `testid:` prefix.
+pub struct TestIdStrategy;
+
+/// The attributes teams commonly use to give elements a test id.
+const TEST_ID_ATTRIBUTES: [&str; 5] = [
+    "data-testid",
+    "data-test-id",
+    "data-test",
+    "data-qa",
+    "data-cy",
+];
+
+#[async_trait]
+impl LocatorStrategy for TestIdStrategy {
+    fn name(&self) -> &str {
+        "testid"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let condition = TEST_ID_ATTRIBUTES
+            .iter()
+            .map(|attribute| format!("@{}={}", attribute, xpath_literal(locator)))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        root.displayed(root.xpath(&format!("//*[{}]", condition)))
+            .await
+    }
+}
+
+/// Matches the form control labelled by the locator: through a `<label>` (by its `for`
+/// attribute or by containing the control), `aria-labelledby` or `aria-label`.
+/// Only used with the `label:` prefix.
+pub struct LabelStrategy;
+
+#[async_trait]
+impl LocatorStrategy for LabelStrategy {
+    fn name(&self) -> &str {
+        "label"
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        let mut controls = vec![];
+
+        let labels = root
+            .all(root.xpath(&format!(
+                "//label[normalize-space(.)={}]",
+                xpath_literal(locator)
+            )))
+            .await?;
+        for label in labels {
+            match label.attr("for").await? {
+                // The control a label points to may be anywhere on the page
+                Some(for_attr) => controls.extend(
+                    root.handle()
+                        .query(By::Id(&for_attr))
+                        .nowait()
+                        .all()
+                        .await?,
+                ),
+                None => controls.extend(
+                    label
+                        .query(By::XPath(".//input | .//select | .//textarea"))
+                        .nowait()
+                        .all()
+                        .await?,
+                ),
+            }
+        }
+
+        controls.extend(
+            root.displayed(root.xpath(&format!(
+                "//*[@aria-labelledby=//*[normalize-space(.)={}]/@id]",
+                xpath_literal(locator)
+            )))
+            .await?,
+        );
+        controls.extend(
+            root.displayed(root.xpath(&format!("//*[@aria-label={}]", xpath_literal(locator))))
+                .await?,
+        );
+        Ok(controls)
+    }
+}
+
+/// Matches elements with an ARIA role, explicit or implied by the tag (`<button>`, `<a href>`,
+/// `<input type="checkbox">`, ...), by their accessible name: the text a screen reader would
+/// announce, from `aria-labelledby`, `aria-label`, a `<label>`, or the element's content.
+/// Exact names are preferred over partial ones. Only used with a role, like `locate button "Submit"`.
+pub struct RoleStrategy {
+    role: String,
+}
+
+impl RoleStrategy {
+    /// A strategy for elements with the given role, which is also its name.
+    pub fn new(role: &str) -> Self {
+        Self {
+            role: role.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl LocatorStrategy for RoleStrategy {
+    fn name(&self) -> &str {
+        &self.role
+    }
+
+    async fn find(&self, root: &SearchRoot, locator: &str) -> Result<Vec<WebElement>> {
+        Ok(root
+            .handle()
+            .execute(
+                &format!("{}{}{}", DOM_FUNCTIONS, ROLE_FUNCTIONS, FIND_BY_ROLE),
+                vec![root.to_json()?, self.role.clone().into(), locator.into()],
+            )
+            .await?
+            .elements()?)
+    }
+}
@@ -2,9 +2,13 @@
 
 use std::fmt::Display;
 
-use crate::scanner::{Token, TokenType};
+use crate::{
+    error::ErrorKind,
+    locator::{Relation, ROLES},
+    scanner::{Token, TokenType},
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
 /// Represents the different kinds of statements in SchnauzerUI
 #[derive(Debug, Clone, PartialEq)]
@@ -31,6 +35,13 @@ pub enum Stmt {
     /// ```
     SetVariable(SetVariableStmt),
 
+    /// Change one of the interpreter's settings for the rest of the script.
+    /// # Example
+    /// ```sui
+    /// set drag-mode "pointer"
+    /// ```
+    Setting(SettingStmt),
+
     /// A Schnauzer UI comment.
     /// Comments are automatically added to
     /// test reports.
@@ -54,7 +65,13 @@ pub enum Stmt {
     /// # Script skips ahead to here
     /// catch-error: screenshot
     /// ```
-    CatchErr(CmdStmt),
+    ///
+    /// A handler can name the kinds of error it handles (see `ErrorKind`).
+    /// Errors of other kinds skip ahead to the next catch-error.
+    /// ```sui
+    /// catch-error "not-found" "timeout": refresh and try-again
+    /// ```
+    CatchErr(Vec<ErrorKind>, CmdStmt),
 
     /// Change SchnauzerUIs locate command from starting
     /// at the top of the document to starting at a particular element
@@ -64,7 +81,7 @@ pub enum Stmt {
     /// ```sui
     /// under "Navigation" locate "Desired Text" and click
     /// ```
-    Under(CmdParam, CmdStmt),
+    Under(Locator, CmdStmt),
 
     /// The same as `Under`, but starts the search at the currently
     /// located element.
@@ -75,9 +92,48 @@ pub enum Stmt {
     /// ```
     UnderActiveElement(CmdStmt),
 
-    /// This statement is not meant to be parsed. It is added by the interpreter
-    /// as part of try-again logic.
-    SetHadErrorFieldToFalse,
+    /// Search for elements inside the located element, like `under`, for every
+    /// statement until the matching `end`. Blocks can be nested.
+    ///
+    /// # Example
+    /// ```sui
+    /// within "Edit user"
+    /// locate "First name" and type "Ada"
+    /// locate "Save" and click
+    /// end
+    /// ```
+    Within(Locator),
+
+    /// Closes the innermost `within` block.
+    End,
+
+    /// A statement whose failure is recorded in the report, but doesn't stop the script
+    /// or skip ahead to a catch-error.
+    ///
+    /// # Example
+    /// ```sui
+    /// soft locate "Welcome back!"
+    /// ```
+    Soft(Box<Stmt>),
+
+    /// Starts the cleanup section. The statements after it always run at the end of the
+    /// script, even when an earlier statement failed. `finally:` works too.
+    ///
+    /// # Example
+    /// ```sui
+    /// cleanup:
+    /// locate "Delete test user" and click
+    /// ```
+    Cleanup,
+
+    /// Limits how many seconds each statement after it can run before it fails with a
+    /// timeout. `timeout "unlimited"` lifts the limit.
+    ///
+    /// # Example
+    /// ```sui
+    /// timeout "30"
+    /// ```
+    Timeout(CmdParam),
 }
 
 impl Display for Stmt {
@@ -86,11 +142,22 @@ impl Display for Stmt {
             Stmt::Cmd(cs) => write!(f, "{}", cs),
             Stmt::If(is) => write!(f, "{}", is),
             Stmt::SetVariable(sv) => write!(f, "{}", sv),
+            Stmt::Setting(ss) => write!(f, "{}", ss),
             Stmt::Comment(s) => write!(f, "{}", s),
-            Stmt::CatchErr(cs) => write!(f, "catch-error: {}", cs),
-            Stmt::SetHadErrorFieldToFalse => write!(f, ""),
-            Stmt::Under(cp, cs) => write!(f, "under {} {}", cp, cs),
+            Stmt::CatchErr(kinds, cs) => {
+                write!(f, "catch-error")?;
+                for kind in kinds {
+                    write!(f, " \"{}\"", kind)?;
+                }
+                write!(f, ": {}", cs)
+            }
+            Stmt::Under(locator, cs) => write!(f, "under {} {}", locator, cs),
             Stmt::UnderActiveElement(cs) => write!(f, "under-active-element {}", cs),
+            Stmt::Within(locator) => write!(f, "within {}", locator),
+            Stmt::End => write!(f, "end"),
+            Stmt::Soft(stmt) => write!(f, "soft {}", stmt),
+            Stmt::Cleanup => write!(f, "cleanup:"),
+            Stmt::Timeout(cp) => write!(f, "timeout {}", cp),
         }
     }
 }
@@ -111,6 +178,22 @@ impl Display for SetVariableStmt {
     }
 }
 
+/// Change the setting with the given name to the given value
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingStmt {
+    /// The name of the setting
+    pub name: String,
+
+    /// The new value of the setting
+    pub value: CmdParam,
+}
+
+impl Display for SettingStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "set {} {}", self.name, self.value)
+    }
+}
+
 /// Conditiionally execute a command statement
 #[derive(Debug, Clone, PartialEq)]
 pub struct IfStmt {
@@ -161,13 +244,16 @@ impl Display for CmdStmt {
 pub enum Cmd {
     /// Command for resolving a locator to a web element.
     /// Also scrolls the element into view.
-    /// The associated string is the locator.
-    Locate(CmdParam),
+    Locate(Locator),
 
     /// Command for resolving a locator to a web element.
     /// Does not scroll the element into view.
-    /// The associated string is the locator.
-    LocateNoScroll(CmdParam),
+    LocateNoScroll(Locator),
+
+    /// Command for locating the table row whose cell in a column has the given text.
+    /// The rest of the statement searches from the row, like `under`.
+    /// The associated params are the column header and the cell text.
+    LocateRow(CmdParam, CmdParam),
 
     /// Command for typing text into some web element.
     /// The associated string is the provided text.
@@ -180,7 +266,9 @@ pub enum Cmd {
     Refresh,
 
     /// The try again command lets the process know to start over after the last error handling line.
-    TryAgain,
+    /// The first param is how many times to retry, and the second how many seconds to wait
+    /// before the first retry (doubling for each one after).
+    TryAgain(Option<CmdParam>, Option<CmdParam>),
 
     /// Command for taking a screenshot
     Screenshot,
@@ -203,13 +291,31 @@ pub enum Cmd {
     /// The associated String is the option text.
     Select(CmdParam),
 
-    /// Command for simulating drag and drop behavior with JavaScript.
+    /// Command for dragging the located element onto another element, either by
+    /// simulating HTML5 drag events with JavaScript or with real pointer actions.
     /// The associated String is the locator for the target element.
     DragTo(CmdParam),
 
-    /// Command for uploading a file. Associated text is the path
-    /// to the file to upload.
-    Upload(CmdParam),
+    /// Command for dragging the located element by an offset with real
+    /// pointer actions, like moving a slider handle.
+    /// The associated params are the x and y offsets in pixels.
+    DragBy(CmdParam, CmdParam),
+
+    /// Command for reading the located html table into a variable.
+    /// Associated string is the variable name.
+    ReadTableTo(String),
+
+    /// Command for uploading one or more files. Associated params are the paths
+    /// to the files to upload.
+    Upload(Vec<CmdParam>),
+
+    /// Command for waiting until the browser finishes downloading a file.
+    /// The associated param is a file name pattern like `*.csv`.
+    ExpectDownload(CmdParam),
+
+    /// Command for reading the text of the last expected download to a variable.
+    /// Associated string is the variable name.
+    ReadDownloadTo(String),
 
     /// Command for accepting a browser alert window.
     AcceptAlert,
@@ -221,26 +327,121 @@ pub enum Cmd {
 impl Display for Cmd {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Cmd::Locate(cp) => write!(f, "locate {}", cp),
+            Cmd::Locate(locator) => write!(f, "locate {}", locator),
             Cmd::Type(cp) => write!(f, "type {}", cp),
             Cmd::Click => write!(f, "click"),
             Cmd::Refresh => write!(f, "refresh"),
-            Cmd::TryAgain => write!(f, "try-again"),
+            Cmd::TryAgain(retries, backoff) => {
+                write!(f, "try-again")?;
+                if let Some(retries) = retries {
+                    write!(f, " {}", retries)?;
+                }
+                if let Some(backoff) = backoff {
+                    write!(f, " backoff {} seconds", backoff)?;
+                }
+                Ok(())
+            }
             Cmd::Screenshot => write!(f, "screenshot"),
             Cmd::ReadTo(cp) => write!(f, "read-to {}", cp),
             Cmd::Url(cp) => write!(f, "url {}", cp),
             Cmd::Press(cp) => write!(f, "press {}", cp),
             Cmd::Chill(cp) => write!(f, "chill {}", cp),
-            Cmd::LocateNoScroll(cp) => write!(f, "locate-no-scroll {}", cp),
+            Cmd::LocateNoScroll(locator) => write!(f, "locate-no-scroll {}", locator),
+            Cmd::LocateRow(column, value) => write!(f, "locate-row where {} is {}", column, value),
             Cmd::Select(cp) => write!(f, "select {}", cp),
             Cmd::DragTo(cp) => write!(f, "drag-to {}", cp),
-            Cmd::Upload(cp) => write!(f, "upload {}", cp),
+            Cmd::DragBy(x, y) => write!(f, "drag-by {} {}", x, y),
+            Cmd::ReadTableTo(var) => write!(f, "read-table-to {}", var),
+            Cmd::Upload(cps) => write!(
+                f,
+                "upload {}",
+                cps.iter()
+                    .map(|cp| cp.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Cmd::ExpectDownload(cp) => write!(f, "expect-download {}", cp),
+            Cmd::ReadDownloadTo(var) => write!(f, "read-download-to {}", var),
             Cmd::AcceptAlert => write!(f, "accept-alert"),
             Cmd::DismissAlert => write!(f, "dismiss-alert"),
         }
     }
 }
 
+/// Describes which element the locate command should find.
+///
+/// ```sui
+/// locate "Edit"
+/// locate "Edit" number "3"
+/// locate last "Delete"
+/// locate button "Submit"
+/// locate "Report ready" within "60" seconds
+/// locate "Edit" right-of "Jane Doe"
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locator {
+    /// The text to match against the page
+    pub text: CmdParam,
+
+    /// Which of several matching elements to pick. When `None`, the first is used.
+    pub ordinal: Option<Ordinal>,
+
+    /// An ARIA role the element must have, in which case the text is matched against its
+    /// accessible name (see `locator::ROLES`).
+    pub role: Option<String>,
+
+    /// Another locator the element must be positioned relative to on screen. Matches are
+    /// ranked by their distance from this anchor element.
+    pub relation: Option<(Relation, CmdParam)>,
+
+    /// How many seconds to keep looking for the element, instead of the `locate-timeout` setting.
+    pub timeout: Option<CmdParam>,
+}
+
+impl From<CmdParam> for Locator {
+    fn from(text: CmdParam) -> Self {
+        Self {
+            text,
+            ordinal: None,
+            role: None,
+            relation: None,
+            timeout: None,
+        }
+    }
+}
+
+impl Display for Locator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(Ordinal::Last) = self.ordinal {
+            write!(f, "last ")?;
+        }
+        if let Some(ref role) = self.role {
+            write!(f, "{} ", role)?;
+        }
+        write!(f, "{}", self.text)?;
+        if let Some(Ordinal::Number(ref n)) = self.ordinal {
+            write!(f, " number {}", n)?;
+        }
+        if let Some((ref relation, ref anchor)) = self.relation {
+            write!(f, " {} {}", relation, anchor)?;
+        }
+        if let Some(ref timeout) = self.timeout {
+            write!(f, " within {} seconds", timeout)?;
+        }
+        Ok(())
+    }
+}
+
+/// Picks one of several elements matching a locator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ordinal {
+    /// The element at a position, counting from 1
+    Number(CmdParam),
+
+    /// The last matching element
+    Last,
+}
+
 /// Represents the kinds of parameters a SchnauzerUI command can have
 #[derive(Debug, Clone, PartialEq)]
 pub enum CmdParam {
@@ -249,6 +450,10 @@ pub enum CmdParam {
 
     /// A variable
     Variable(String),
+
+    /// A variable holding structured data, indexed into
+    /// like `results[2]["Status"]`
+    Indexed(String, Vec<Index>),
 }
 
 impl Display for CmdParam {
@@ -256,6 +461,13 @@ impl Display for CmdParam {
         match self {
             CmdParam::String(s) => write!(f, "\"{}\"", s),
             CmdParam::Variable(v) => write!(f, "{}", v),
+            CmdParam::Indexed(v, indices) => {
+                write!(f, "{}", v)?;
+                for index in indices {
+                    write!(f, "[{}]", index)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -266,12 +478,69 @@ impl TryFrom<Token> for CmdParam {
     fn try_from(value: Token) -> Result<Self, Self::Error> {
         match value.token_type {
             TokenType::StringLiteral => Ok(Self::String(value.lexeme)),
+            TokenType::Variable if value.lexeme.contains('[') => parse_indexed_variable(&value),
             TokenType::Variable => Ok(Self::Variable(value.lexeme)),
             _ => bail!("A CmdParam can only be constructed from a StringLiteral or a Variable"),
         }
     }
 }
 
+/// One step into a structured variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Index {
+    /// A position, like a row number, counting from 1
+    Position(usize),
+
+    /// A quoted key, like a column header
+    Key(String),
+}
+
+impl Display for Index {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Index::Position(n) => write!(f, "{}", n),
+            Index::Key(k) => write!(f, "\"{}\"", k),
+        }
+    }
+}
+
+/// Splits a variable token like `results[2]["Status"]` into its name and indices.
+fn parse_indexed_variable(token: &Token) -> Result<CmdParam> {
+    let Some((name, mut rest)) = token.lexeme.split_once('[') else {
+        bail!(token.error("Expected an index like [1] or [\"Header\"]"))
+    };
+
+    let mut indices = vec![];
+    loop {
+        let Some((index, remaining)) = rest.split_once(']') else {
+            bail!(token.error("Expected a closing ]"))
+        };
+        if let Some(key) = index.strip_prefix('"').and_then(|i| i.strip_suffix('"')) {
+            indices.push(Index::Key(key.to_owned()));
+        } else if let Ok(position) = index.parse::<usize>() {
+            if position == 0 {
+                bail!(token.error("Positions count from 1"));
+            }
+            indices.push(Index::Position(position));
+        } else {
+            bail!(token.error(format!(
+                "Expected a number or quoted text between [ and ], but got {}",
+                index
+            )));
+        }
+
+        if remaining.is_empty() {
+            break;
+        }
+        let Some(remaining) = remaining.strip_prefix('[') else {
+            bail!(token.error("Expected another index or the end of the variable"))
+        };
+        rest = remaining;
+    }
+
+    Ok(CmdParam::Indexed(name.to_owned(), indices))
+}
+
 /// The Parser is responsible for transforming a list of SchnauzerUI tokens
 /// in an AST.
 #[derive(Debug)]
@@ -284,6 +553,9 @@ pub struct Parser {
 
     /// Tracks the current index
     index: usize,
+
+    /// Whether `within` blocks may be left open (see `allow_open_blocks`).
+    allow_open_blocks: bool,
 }
 
 impl Default for Parser {
@@ -299,9 +571,17 @@ impl Parser {
             stmts: vec![],
             curr_line: vec![],
             index: 0,
+            allow_open_blocks: false,
         }
     }
 
+    /// Don't require every `within` to be closed by an `end` in the same source. The REPL
+    /// parses a line at a time, so a block opened on one line is closed on a later one.
+    pub fn allow_open_blocks(&mut self) -> &mut Self {
+        self.allow_open_blocks = true;
+        self
+    }
+
     /// Transform a list of tokens into a list of statements.
     pub fn parse(&mut self, tokens: Vec<Token>) -> Result<Vec<Stmt>> {
         // A token list passed to the parse should always end in an Eof token.
@@ -309,6 +589,12 @@ impl Parser {
         assert!(!tokens.is_empty() && tokens.last().unwrap().token_type == TokenType::Eof);
         let mut errors = vec![];
 
+        // The `within` tokens of blocks which haven't been closed yet
+        let mut open_blocks = vec![];
+
+        // Whether the cleanup section has started
+        let mut in_cleanup = false;
+
         // Every Schnauzer UI statement is a single line.
         for line in tokens.split(|t| t.token_type == TokenType::Eol) {
             self.curr_line = line.to_vec();
@@ -324,7 +610,34 @@ impl Parser {
 
             // Parse the statement and keep track of an error if it produced one
             match self.parse_stmt() {
-                Ok(stmt) => self.stmts.push(stmt),
+                Ok(stmt) => {
+                    // A script has one cleanup section, which can't start in the middle of a within block
+                    if stmt == Stmt::Cleanup {
+                        if in_cleanup {
+                            errors.push(anyhow!(
+                                line[0].error("A script can only have one cleanup section")
+                            ));
+                        } else if !open_blocks.is_empty() {
+                            errors.push(anyhow!(line[0].error(
+                                "The cleanup section can't start in the middle of a within block. Add an end before it"
+                            )));
+                        }
+                        in_cleanup = true;
+                    }
+
+                    // Pair each end with the within it closes
+                    if let Stmt::Within(_) = stmt {
+                        open_blocks.push(line[0].clone());
+                    } else if stmt == Stmt::End
+                        && open_blocks.pop().is_none()
+                        && !self.allow_open_blocks
+                    {
+                        errors.push(anyhow!(
+                            line[0].error("Found an end without a within block to close")
+                        ));
+                    }
+                    self.stmts.push(stmt)
+                }
                 Err(e) => errors.push(e),
             }
 
@@ -332,6 +645,14 @@ impl Parser {
             self.index = 0;
         }
 
+        if !self.allow_open_blocks {
+            for token in open_blocks {
+                errors.push(anyhow!(token.error(
+                    "This within block is never closed. Add an end after its last statement"
+                )));
+            }
+        }
+
         // Reset the parser in case we want to reuse it.
         let stmts = self.stmts.clone();
         self.stmts.clear();
@@ -354,22 +675,54 @@ impl Parser {
         if self.advance_on(TokenType::If).is_ok() {
             self.parse_if_stmt().map(Stmt::If)
         } else if self.advance_on(TokenType::Under).is_ok() {
-            let cp = self.parse_cmd_param()?;
+            let locator = self.parse_locator()?;
             let cs = self.parse_cmd_stmt()?;
-            Ok(Stmt::Under(cp, cs))
+            Ok(Stmt::Under(locator, cs))
         } else if self.advance_on(TokenType::UnderActiveElement).is_ok() {
             let cs = self.parse_cmd_stmt()?;
             Ok(Stmt::UnderActiveElement(cs))
+        } else if self.advance_on_keyword(TokenType::Within).is_ok() {
+            self.parse_locator().map(Stmt::Within)
+        } else if self.advance_on_keyword(TokenType::End).is_ok() {
+            Ok(Stmt::End)
+        } else if self.advance_on_keyword(TokenType::Timeout).is_ok() {
+            self.parse_cmd_param().map(Stmt::Timeout)
+        } else if self.advance_on(TokenType::Cleanup).is_ok() {
+            Ok(Stmt::Cleanup)
+        } else if let Ok(soft_token) = self.advance_on_keyword(TokenType::Soft) {
+            match self.parse_stmt()? {
+                Stmt::Within(_)
+                | Stmt::End
+                | Stmt::CatchErr(..)
+                | Stmt::Soft(_)
+                | Stmt::Cleanup => {
+                    bail!(soft_token.error("Only commands, if and under statements can be soft"))
+                }
+                stmt => Ok(Stmt::Soft(Box::new(stmt))),
+            }
         } else if let Ok(token) = self.advance_on(TokenType::Comment) {
             Ok(Stmt::Comment(token.lexeme))
         } else if self.advance_on(TokenType::CatchError).is_ok() {
+            let mut kinds = vec![];
+            while let Ok(token) = self.advance_on(TokenType::StringLiteral) {
+                let kind = token
+                    .lexeme
+                    .parse::<ErrorKind>()
+                    .map_err(|e| anyhow!(token.error(e)))?;
+                kinds.push(kind);
+            }
+            let _colon_token = self.advance_on(TokenType::Colon)?;
             let stmt = self.parse_cmd_stmt()?;
-            Ok(Stmt::CatchErr(stmt))
+            Ok(Stmt::CatchErr(kinds, stmt))
         } else if self.advance_on(TokenType::Save).is_ok() {
             let value = self.advance_on(TokenType::StringLiteral)?.lexeme;
             let _as_token = self.advance_on(TokenType::As)?;
             let name = self.advance_on(TokenType::Variable)?.lexeme;
             Ok(Stmt::SetVariable(SetVariableStmt { name, value }))
+        } else if self.advance_on(TokenType::Set).is_ok() {
+            let name = self.advance_on(TokenType::Variable)?.lexeme;
+            let value = self.parse_cmd_param()?;
+            Ok(Stmt::Setting(SettingStmt { name, value }))
         } else {
             self.parse_cmd_stmt().map(Stmt::Cmd)
         }
@@ -407,12 +760,88 @@ impl Parser {
             .try_into()
     }
 
+    /// Parse a `Locator`, the text to locate by with an optional role before it,
+    /// either `last` at the start or `number "n"` after it, then an optional
+    /// relation to another element (like `right-of "Name"`) and `within "n" seconds`.
+    fn parse_locator(&mut self) -> Result<Locator> {
+        // `last` reads like a variable too, so it's only `last` if a locator follows it
+        let last = match self.curr_line.get(self.index + 1) {
+            Some(next)
+                if matches!(
+                    next.token_type,
+                    TokenType::StringLiteral | TokenType::Variable
+                ) =>
+            {
+                self.advance_on_keyword(TokenType::Last).is_ok()
+            }
+            _ => false,
+        };
+
+        // A role reads like a variable, so it's only a role if a locator follows it
+        let role = match (self.current_token(), self.curr_line.get(self.index + 1)) {
+            (Some(role), Some(next))
+                if role.token_type == TokenType::Variable
+                    && ROLES.contains(&role.lexeme.as_str())
+                    && matches!(
+                        next.token_type,
+                        TokenType::StringLiteral | TokenType::Variable
+                    ) =>
+            {
+                self.index += 1;
+                Some(role.lexeme)
+            }
+            _ => None,
+        };
+
+        let text = self.parse_cmd_param()?;
+        let ordinal = if last {
+            Some(Ordinal::Last)
+        } else if self.advance_on_keyword(TokenType::Number).is_ok() {
+            Some(Ordinal::Number(self.parse_cmd_param()?))
+        } else {
+            None
+        };
+        let relation = [
+            (TokenType::Near, Relation::Near),
+            (TokenType::RightOf, Relation::RightOf),
+            (TokenType::LeftOf, Relation::LeftOf),
+            (TokenType::Above, Relation::Above),
+            (TokenType::Below, Relation::Below),
+        ]
+        .into_iter()
+        .find_map(|(tt, relation)| self.advance_on_keyword(tt).ok().map(|_| relation));
+        let relation = match relation {
+            Some(relation) => Some((relation, self.parse_cmd_param()?)),
+            None => None,
+        };
+        let timeout = if self.advance_on_keyword(TokenType::Within).is_ok() {
+            let timeout = self.parse_cmd_param()?;
+            self.advance_on_keyword(TokenType::Seconds)?;
+            Some(timeout)
+        } else {
+            None
+        };
+        Ok(Locator {
+            text,
+            ordinal,
+            role,
+            relation,
+            timeout,
+        })
+    }
+
     /// Parse a single SchnauzerUI command.
     fn parse_cmd(&mut self) -> Result<Cmd> {
         if self.advance_on(TokenType::Locate).is_ok() {
-            self.parse_cmd_param().map(Cmd::Locate)
+            self.parse_locator().map(Cmd::Locate)
         } else if self.advance_on(TokenType::LocateNoScroll).is_ok() {
-            self.parse_cmd_param().map(Cmd::LocateNoScroll)
+            self.parse_locator().map(Cmd::LocateNoScroll)
+        } else if self.advance_on(TokenType::LocateRow).is_ok() {
+            let _where_token = self.advance_on_keyword(TokenType::Where)?;
+            let column = self.parse_cmd_param()?;
+            let _is_token = self.advance_on_keyword(TokenType::Is)?;
+            let value = self.parse_cmd_param()?;
+            Ok(Cmd::LocateRow(column, value))
         } else if self.advance_on(TokenType::Type).is_ok() {
             self.parse_cmd_param().map(Cmd::Type)
         } else if self.advance_on(TokenType::ReadTo).is_ok() {
@@ -428,14 +857,44 @@ impl Parser {
             self.parse_cmd_param().map(Cmd::Select)
         } else if self.advance_on(TokenType::DragTo).is_ok() {
             self.parse_cmd_param().map(Cmd::DragTo)
+        } else if self.advance_on(TokenType::DragBy).is_ok() {
+            let x = self.parse_cmd_param()?;
+            let y = self.parse_cmd_param()?;
+            Ok(Cmd::DragBy(x, y))
         } else if self.advance_on(TokenType::Upload).is_ok() {
-            self.parse_cmd_param().map(Cmd::Upload)
+            let mut paths = vec![self.parse_cmd_param()?];
+            while let Ok(path) = self.parse_cmd_param() {
+                paths.push(path);
+            }
+            Ok(Cmd::Upload(paths))
+        } else if self.advance_on(TokenType::ReadTableTo).is_ok() {
+            let var = self.advance_on(TokenType::Variable)?;
+            Ok(Cmd::ReadTableTo(var.lexeme))
+        } else if self.advance_on(TokenType::ExpectDownload).is_ok() {
+            self.parse_cmd_param().map(Cmd::ExpectDownload)
+        } else if self.advance_on(TokenType::TryAgain).is_ok() {
+            let retries = match self.at_backoff() {
+                true => None,
+                false => self.parse_cmd_param().ok(),
+            };
+            let backoff = match self.at_backoff() {
+                true => {
+                    self.advance_on_keyword(TokenType::Backoff)?;
+                    let seconds = self.parse_cmd_param()?;
+                    self.advance_on_keyword(TokenType::Seconds)?;
+                    Some(seconds)
+                }
+                false => None,
+            };
+            Ok(Cmd::TryAgain(retries, backoff))
+        } else if self.advance_on(TokenType::ReadDownloadTo).is_ok() {
+            let var = self.advance_on(TokenType::Variable)?;
+            Ok(Cmd::ReadDownloadTo(var.lexeme))
         } else {
             let token = self.advance_on_any()?;
             match token.token_type {
                 TokenType::Click => Ok(Cmd::Click),
                 TokenType::Refresh => Ok(Cmd::Refresh),
-                TokenType::TryAgain => Ok(Cmd::TryAgain),
                 TokenType::Screenshot => Ok(Cmd::Screenshot),
                 TokenType::AcceptAlert => Ok(Cmd::AcceptAlert),
                 TokenType::DismissAlert => Ok(Cmd::DismissAlert),
@@ -461,6 +920,37 @@ impl Parser {
         }
     }
 
+    /// Same as `advance_on`, for words which are only keywords in one place (like `where` after
+    /// `locate-row`). The scanner reads them as variables, so scripts can still use them as
+    /// variable names everywhere else.
+    fn advance_on_keyword(&mut self, tt: TokenType) -> Result<Token> {
+        let Some(current_token) = self.current_token() else {
+            bail!("No more tokens on this line")
+        };
+        if current_token.token_type == TokenType::Variable && current_token.lexeme == tt.to_string()
+        {
+            self.index += 1;
+            Ok(Token {
+                token_type: tt,
+                ..current_token
+            })
+        } else {
+            bail!(current_token.error(format!("Expected \"{}\"", tt)))
+        }
+    }
+
+    /// Whether the current token is the `backoff` of a `try-again`. It reads like a variable,
+    /// so it's only `backoff` if a number of seconds follows it.
+    fn at_backoff(&self) -> bool {
+        matches!(
+            (self.current_token(), self.curr_line.get(self.index + 1)),
+            (Some(token), Some(next))
+                if token.token_type == TokenType::Variable
+                    && token.lexeme == TokenType::Backoff.to_string()
+                    && matches!(next.token_type, TokenType::StringLiteral | TokenType::Variable)
+        )
+    }
+
     /// Same as `advance_on`, but lets you specify a list of acceptable token types.
     fn advance_on_any_of(&mut self, tts: Vec<TokenType>) -> Result<Token> {
         for tt in tts.clone().into_iter() {
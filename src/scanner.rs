@@ -5,23 +5,45 @@ use std::fmt::Display;
 pub enum TokenType {
     Locate,
     LocateNoScroll,
+    LocateRow,
+    Where,
+    Is,
+    Number,
+    Last,
+    Within,
+    Seconds,
+    Backoff,
+    Near,
+    RightOf,
+    LeftOf,
+    Above,
+    Below,
     Type,
     Click,
     Refresh,
     TryAgain,
     Screenshot,
     CatchError,
+    Colon,
     ReadTo,
     Url,
     Press,
     Chill,
     Select,
     DragTo,
+    DragBy,
     Upload,
+    ReadTableTo,
+    ExpectDownload,
+    ReadDownloadTo,
     AcceptAlert,
     DismissAlert,
     Under,
     UnderActiveElement,
+    End,
+    Soft,
+    Cleanup,
+    Timeout,
     StringLiteral,
     If,
     Then,
@@ -29,6 +51,7 @@ pub enum TokenType {
     Variable,
     Save,
     As,
+    Set,
     Comment,
     Eof,
     Eol,
@@ -45,7 +68,8 @@ impl Display for TokenType {
             TokenType::Refresh => "refresh",
             TokenType::TryAgain => "try-again",
             TokenType::Screenshot => "screenshot",
-            TokenType::CatchError => "catch-error:",
+            TokenType::CatchError => "catch-error",
+            TokenType::Colon => ":",
             TokenType::ReadTo => "read-to",
             TokenType::StringLiteral => "quoted text",
             TokenType::If => "if",
@@ -56,18 +80,40 @@ impl Display for TokenType {
             TokenType::Eol => "eol",
             TokenType::Save => "save",
             TokenType::As => "as",
+            TokenType::Set => "set",
             TokenType::Url => "url",
             TokenType::Comment => "a comment",
             TokenType::Press => "press",
             TokenType::Chill => "chill",
             TokenType::LocateNoScroll => "locate-no-scroll",
+            TokenType::LocateRow => "locate-row",
+            TokenType::Where => "where",
+            TokenType::Is => "is",
+            TokenType::Number => "number",
+            TokenType::Last => "last",
+            TokenType::Within => "within",
+            TokenType::Seconds => "seconds",
+            TokenType::Backoff => "backoff",
+            TokenType::Near => "near",
+            TokenType::RightOf => "right-of",
+            TokenType::LeftOf => "left-of",
+            TokenType::Above => "above",
+            TokenType::Below => "below",
             TokenType::Select => "select",
             TokenType::DragTo => "drag-to",
+            TokenType::DragBy => "drag-by",
             TokenType::Upload => "upload",
+            TokenType::ReadTableTo => "read-table-to",
+            TokenType::ExpectDownload => "expect-download",
+            TokenType::ReadDownloadTo => "read-download-to",
             TokenType::AcceptAlert => "accept-alert",
             TokenType::DismissAlert => "dismiss-alert",
             TokenType::Under => "under",
             TokenType::UnderActiveElement => "under-active-element",
+            TokenType::End => "end",
+            TokenType::Soft => "soft",
+            TokenType::Cleanup => "cleanup:",
+            TokenType::Timeout => "timeout",
         };
 
         write!(f, "{}", lexeme)
@@ -133,6 +179,10 @@ pub struct Scanner {
 
     /// Keeps track of whether we are currently scanning between quotes
     in_quotes: bool,
+
+    /// Keeps track of whether we are currently scanning a variable index
+    /// with a quoted key, like `results[1]["Order Status"]`
+    in_index: bool,
 }
 
 impl Scanner {
@@ -144,6 +194,7 @@ impl Scanner {
             line: 0,
             string_literal_buffer: String::new(),
             in_quotes: false,
+            in_index: false,
         }
     }
 
@@ -187,6 +238,19 @@ impl Scanner {
     /// String literals are sometimes passed by the scan function in pieces (due to splitting on whitespace),
     /// so the function returns None while it is in the process of rejoining those string literals.
     fn resolve_token(&mut self, lexeme: &str) -> Option<Token> {
+        // If we're rejoining a variable index, keep going until its key's closing quote
+        if self.in_index {
+            self.string_literal_buffer.push_str(lexeme);
+            if lexeme.matches('\"').count() % 2 == 1 {
+                self.in_index = false;
+                let res = self.string_literal_buffer.clone();
+                self.string_literal_buffer.clear();
+                return Some(self.token(TokenType::Variable, res));
+            }
+            self.string_literal_buffer.push(' ');
+            return None;
+        }
+
         match lexeme {
             // Commands
             "locate" if !self.in_quotes => Some(self.token(TokenType::Locate, "locate".into())),
@@ -199,7 +263,12 @@ impl Scanner {
             "screenshot" if !self.in_quotes => {
                 Some(self.token(TokenType::Screenshot, "screenshot".into()))
             }
+            // The colon ends the list of error kinds a catch-error handles, if there is one
             "catch-error:" if !self.in_quotes => {
+                self.add_token(TokenType::CatchError, "catch-error".into());
+                Some(self.token(TokenType::Colon, ":".into()))
+            }
+            "catch-error" if !self.in_quotes => {
                 Some(self.token(TokenType::CatchError, "catch-error".into()))
             }
             "if" if !self.in_quotes => Some(self.token(TokenType::If, "if".into())),
@@ -208,15 +277,29 @@ impl Scanner {
             "read-to" if !self.in_quotes => Some(self.token(TokenType::ReadTo, "read-to".into())),
             "save" if !self.in_quotes => Some(self.token(TokenType::Save, "save".into())),
             "as" if !self.in_quotes => Some(self.token(TokenType::As, "as".into())),
+            "set" if !self.in_quotes => Some(self.token(TokenType::Set, "set".into())),
             "url" if !self.in_quotes => Some(self.token(TokenType::Url, "url".into())),
             "press" if !self.in_quotes => Some(self.token(TokenType::Press, "press".into())),
             "chill" if !self.in_quotes => Some(self.token(TokenType::Chill, "chill".into())),
             "locate-no-scroll" if !self.in_quotes => {
                 Some(self.token(TokenType::LocateNoScroll, "locate-no-scroll".into()))
             }
+            "locate-row" if !self.in_quotes => {
+                Some(self.token(TokenType::LocateRow, "locate-row".into()))
+            }
             "select" if !self.in_quotes => Some(self.token(TokenType::Select, "select".into())),
             "drag-to" if !self.in_quotes => Some(self.token(TokenType::DragTo, "drag-to".into())),
+            "drag-by" if !self.in_quotes => Some(self.token(TokenType::DragBy, "drag-by".into())),
             "upload" if !self.in_quotes => Some(self.token(TokenType::Upload, "upload".into())),
+            "read-table-to" if !self.in_quotes => {
+                Some(self.token(TokenType::ReadTableTo, "read-table-to".into()))
+            }
+            "expect-download" if !self.in_quotes => {
+                Some(self.token(TokenType::ExpectDownload, "expect-download".into()))
+            }
+            "read-download-to" if !self.in_quotes => {
+                Some(self.token(TokenType::ReadDownloadTo, "read-download-to".into()))
+            }
             "accept-alert" if !self.in_quotes => {
                 Some(self.token(TokenType::AcceptAlert, "accept-alert".into()))
             }
@@ -227,6 +310,20 @@ impl Scanner {
             "under-active-element" if !self.in_quotes => {
                 Some(self.token(TokenType::UnderActiveElement, "under-active-element".into()))
             }
+            "cleanup:" | "finally:" if !self.in_quotes => {
+                Some(self.token(TokenType::Cleanup, "cleanup:".into()))
+            }
+            // A string literal ending a catch-error's list of error kinds, like "not-found":
+            word if word.starts_with('\"')
+                && word.ends_with("\":")
+                && !self.in_quotes
+                && word.len() > 2 =>
+            {
+                let word = word[1..word.len() - 2].to_owned();
+                self.add_token(TokenType::StringLiteral, word);
+                Some(self.token(TokenType::Colon, ":".into()))
+            }
+
             // If we get an entire string literal, stript the quotes and construct the token
             word if word.starts_with('\"')
                 && word.ends_with('\"')
@@ -287,6 +384,15 @@ impl Scanner {
                 None
             }
 
+            // If we get the first part of a variable index whose key was split by whitespace,
+            // switch to index building mode
+            word if word.contains("[\"") && word.matches('\"').count() % 2 == 1 => {
+                self.in_index = true;
+                self.string_literal_buffer.push_str(word);
+                self.string_literal_buffer.push(' ');
+                None
+            }
+
             // If it's not a string literal or a keyword, it's a variable.
             word => Some(self.token(TokenType::Variable, word.into())),
         }
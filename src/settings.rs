@@ -0,0 +1,190 @@
+//! Settings change how the interpreter behaves for the rest of a script.
+//! They are changed from a script with the `set` statement.
+//!
+//! ```sui
+//! set drag-mode "pointer"
+//! ```
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::locator::Locators;
+
+/// The strategy the `drag-to` command uses to move an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragMode {
+    /// Use the JavaScript HTML5 simulation for elements marked `draggable="true"`,
+    /// and real pointer actions for everything else.
+    Auto,
+
+    /// Always fake HTML5 drag events with JavaScript.
+    Html5,
+
+    /// Always press, move and release the mouse with WebDriver actions.
+    Pointer,
+}
+
+/// What `locate` does when a locator matches more than one displayed element
+/// and no ordinal (like `number "2"`) says which one to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbiguityMode {
+    /// Use the first match and add a warning to the report.
+    Warn,
+
+    /// Fail the locate command.
+    Fail,
+}
+
+/// Where `under` looks when nothing under its element matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnderMode {
+    /// Search under the element's parent, then its grandparent and so on, up to the `under-radius`
+    /// setting. With no radius, the whole page (or `within` block) is searched last.
+    Radiate,
+
+    /// Only search under the element itself.
+    Strict,
+}
+
+/// The settings the interpreter consults while executing a script.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// How `drag-to` performs the drag.
+    pub drag_mode: DragMode,
+
+    /// The strategies `locate` tries, in order.
+    pub locators: Locators,
+
+    /// How `locate` handles a locator matching several elements.
+    pub ambiguous_locators: AmbiguityMode,
+
+    /// How long `locate` keeps looking for an element before failing.
+    pub locate_timeout: Duration,
+
+    /// How long `locate` waits between looks.
+    pub poll_interval: Duration,
+
+    /// Whether `under` searches outward from its element.
+    pub under_mode: UnderMode,
+
+    /// How many levels of ancestors `under` searches outward through, or None for no limit.
+    pub under_radius: Option<usize>,
+
+    /// How long a statement can run before it fails with a timeout, or None for no limit.
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            drag_mode: DragMode::Auto,
+            locators: Locators::default(),
+            ambiguous_locators: AmbiguityMode::Warn,
+            locate_timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(500),
+            under_mode: UnderMode::Radiate,
+            under_radius: None,
+            statement_timeout: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Change a setting by name, as written in a `set` statement.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "drag-mode" => {
+                self.drag_mode = match value {
+                    "auto" => DragMode::Auto,
+                    "html5" => DragMode::Html5,
+                    "pointer" => DragMode::Pointer,
+                    _ => bail!(
+                        "Unknown drag-mode \"{}\". Expected \"auto\", \"html5\" or \"pointer\"",
+                        value
+                    ),
+                }
+            }
+            "locator-order" => {
+                let names = value.split(',').map(str::trim).collect::<Vec<_>>();
+                self.locators.set_order(&names)?
+            }
+            "ambiguous-locators" => {
+                self.ambiguous_locators = match value {
+                    "warn" => AmbiguityMode::Warn,
+                    "fail" => AmbiguityMode::Fail,
+                    _ => bail!(
+                        "Unknown ambiguous-locators mode \"{}\". Expected \"warn\" or \"fail\"",
+                        value
+                    ),
+                }
+            }
+            "text-matching" => match value {
+                "loose" => self.locators.set_strict(false),
+                "strict" => self.locators.set_strict(true),
+                _ => bail!(
+                    "Unknown text-matching mode \"{}\". Expected \"loose\" or \"strict\"",
+                    value
+                ),
+            },
+            "locate-timeout" => self.locate_timeout = parse_seconds(value)?,
+            "poll-interval" => {
+                let millis = value.parse::<u64>().with_context(|| {
+                    format!(
+                        "Expected poll-interval to be a number of milliseconds, found \"{}\"",
+                        value
+                    )
+                })?;
+                self.poll_interval = Duration::from_millis(millis)
+            }
+            "under-mode" => {
+                self.under_mode = match value {
+                    "radiate" => UnderMode::Radiate,
+                    "strict" => UnderMode::Strict,
+                    _ => bail!(
+                        "Unknown under-mode \"{}\". Expected \"radiate\" or \"strict\"",
+                        value
+                    ),
+                }
+            }
+            "under-radius" => {
+                self.under_radius = match value {
+                    "unlimited" => None,
+                    levels => Some(levels.parse::<usize>().with_context(|| {
+                        format!(
+                            "Expected under-radius to be a number of levels or \"unlimited\", found \"{}\"",
+                            value
+                        )
+                    })?),
+                }
+            }
+            "statement-timeout" => {
+                self.statement_timeout = match value {
+                    "unlimited" => None,
+                    secs => Some(parse_seconds(secs)?),
+                }
+            }
+            _ => bail!("Unknown setting \"{}\"", name),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a number of seconds, like `"10"` or `"0.5"`.
+pub fn parse_seconds(value: &str) -> Result<Duration> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .with_context(|| format!("Expected a number of seconds, found \"{}\"", value))
+}
+
+/// Write a number of seconds for a message, like `"1 second"` or `"2.5 seconds"`.
+pub fn format_seconds(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    format!(
+        "{} {}",
+        secs,
+        if secs == 1.0 { "second" } else { "seconds" }
+    )
+}
@@ -15,6 +15,70 @@ pub struct ExecutedStmt {
     /// Path to screenshots generated as part of the command exucution,
     /// saved as png.
     pub screenshots: Vec<Vec<u8>>,
+
+    /// Paths to files the browser downloaded during the statement.
+    /// They are copied into the report's `downloads` directory.
+    #[serde(default)]
+    pub downloads: Vec<Utf8PathBuf>,
+
+    /// Things that didn't stop the statement but might not have gone as
+    /// intended, like a locator matching several elements.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+
+    /// Things worth knowing about how the statement was carried out, like
+    /// which input was used for a located label.
+    #[serde(default)]
+    pub notes: Vec<String>,
+
+    /// Locators which no longer matched, where the element they found on
+    /// an earlier run was used instead (see the `healing` module).
+    #[serde(default)]
+    pub healed: Vec<HealedLocator>,
+
+    /// Which retry (counting from 1) of a `try-again` the statement ran in,
+    /// or None for the first attempt.
+    #[serde(default)]
+    pub retry: Option<usize>,
+
+    /// What the page looked like when the statement failed. The screenshot
+    /// is the last of the statement's screenshots.
+    #[serde(default)]
+    pub artifacts: Option<FailureArtifacts>,
+
+    /// Whether the statement ran in the script's cleanup section.
+    #[serde(default)]
+    pub cleanup: bool,
+
+    /// Whether the statement was skipped while looking for a `catch-error`
+    /// to handle an earlier failure, so it never ran.
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+/// What the page looked like when a statement failed, so failures can be
+/// debugged after a headless run. Each part is missing if the browser couldn't provide it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FailureArtifacts {
+    /// The url of the page
+    pub url: Option<String>,
+
+    /// The title of the page
+    pub title: Option<String>,
+
+    /// The page's HTML. It's saved next to the report in the `pages` directory.
+    pub page_source: Option<String>,
+}
+
+/// A locator which stopped matching, and was healed by using the element
+/// most like the one it found on an earlier run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealedLocator {
+    /// The locator as written in the script
+    pub locator: String,
+
+    /// A locator which finds the element used instead, to update the script with
+    pub suggestion: Option<String>,
 }
 
 /// A report which gets passed through the Interpreter and is enriched
@@ -38,6 +102,11 @@ pub struct StandardReport {
 
     /// Whether or tnot the test was forced to exit early due to an error
     pub exited_early: bool,
+
+    /// Problems with the run itself which didn't fail the script, like the
+    /// locator cache not being saved.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 impl StandardReport {
@@ -49,9 +118,18 @@ impl StandardReport {
             date_time: Utc::now().to_string(),
             executed_stmts: vec![],
             exited_early: false,
+            warnings: vec![],
         }
     }
 
+    /// The statements which failed, including soft failures the script kept going after.
+    pub fn failures(&self) -> Vec<&ExecutedStmt> {
+        self.executed_stmts
+            .iter()
+            .filter(|stmt| stmt.error.is_some())
+            .collect()
+    }
+
     /// Set the name of the test run
     pub fn set_testname(&mut self, name: String) -> &mut Self {
         self.name = name;
@@ -67,6 +145,8 @@ impl StandardReport {
     /// Write all the expected ouput of a standard report
     pub fn write_report_default_styling(&mut self) -> Result<()> {
         self.save_screenhots()?;
+        self.save_downloads()?;
+        self.save_failed_pages()?;
         self.write_html_output()?;
         self.write_json_output()
     }
@@ -89,6 +169,58 @@ impl StandardReport {
         Ok(())
     }
 
+    /// Copy any downloaded files next to the report.
+    fn save_downloads(&mut self) -> Result<()> {
+        let downloads = self
+            .executed_stmts
+            .iter()
+            .flat_map(|stmt| stmt.downloads.iter())
+            .collect::<Vec<_>>();
+        if downloads.is_empty() {
+            return Ok(());
+        }
+
+        let downloads_dir = self.output_dir.join("downloads");
+        std::fs::create_dir_all(&downloads_dir)
+            .context(format!("Could not create directory: {}", downloads_dir))?;
+        for download in downloads {
+            let Some(filename) = download.file_name() else {
+                continue;
+            };
+            let destination = downloads_dir.join(filename);
+
+            // The browser may have been saving straight into the report directory
+            if destination.canonicalize_utf8().ok() == download.canonicalize_utf8().ok() {
+                continue;
+            }
+            std::fs::copy(download, &destination)
+                .context(format!("Could not copy download {}", download))?;
+        }
+        Ok(())
+    }
+
+    /// Save the HTML of the pages statements failed on.
+    fn save_failed_pages(&mut self) -> Result<()> {
+        let pages = self
+            .executed_stmts
+            .iter()
+            .filter_map(|stmt| stmt.artifacts.as_ref()?.page_source.as_ref())
+            .collect::<Vec<_>>();
+        if pages.is_empty() {
+            return Ok(());
+        }
+
+        let pages_dir = self.output_dir.join("pages");
+        std::fs::create_dir_all(&pages_dir)
+            .context(format!("Could not create directory: {}", pages_dir))?;
+        for (i, page) in pages.into_iter().enumerate() {
+            let filename = format!("{}_failure_{}.html", self.name, i + 1);
+            std::fs::write(pages_dir.join(filename), page)
+                .context("Could not write page source")?;
+        }
+        Ok(())
+    }
+
     /// Write the report to a json file
     fn write_json_output(&mut self) -> Result<()> {
         self.output_dir.push(format!("{}.json", self.name));
@@ -1,10 +1,14 @@
 //! This module contains code for working with `thirtyfour::WebDriver`s
 
 use anyhow::{Context, Result};
+use camino::Utf8PathBuf;
 use clap::ValueEnum;
 use serde::Deserialize;
-use std::{collections::HashMap, fmt::Display};
-use thirtyfour::{DesiredCapabilities, WebDriver};
+use serde_json::json;
+use std::fmt::Display;
+use thirtyfour::{
+    common::capabilities::firefox::FirefoxPreferences, DesiredCapabilities, WebDriver,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
 pub enum SupportedBrowser {
@@ -21,11 +25,15 @@ impl Display for SupportedBrowser {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WebDriverConfig {
     pub port: usize,
     pub headless: bool,
     pub browser: SupportedBrowser,
+
+    /// Where the browser should save downloaded files, without prompting.
+    /// When `None`, the browser's own default is used.
+    pub download_directory: Option<Utf8PathBuf>,
 }
 
 impl Default for WebDriverConfig {
@@ -34,6 +42,7 @@ impl Default for WebDriverConfig {
             port: 4444,
             headless: false,
             browser: SupportedBrowser::Firefox,
+            download_directory: None,
         }
     }
 }
@@ -43,15 +52,41 @@ pub async fn new_driver(
         port,
         headless,
         browser,
+        download_directory,
     }: WebDriverConfig,
 ) -> Result<WebDriver> {
     let localhost = format!("http://localhost:{}", port);
+
+    // Browsers only accept absolute download paths, so make sure it exists and resolve it.
+    let download_directory = match download_directory {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Could not create download directory: {}", dir))?;
+            Some(dir.canonicalize_utf8()?)
+        }
+        None => None,
+    };
+
     match browser {
         SupportedBrowser::Firefox => {
             let mut caps = DesiredCapabilities::firefox();
             if headless {
                 caps.set_headless()?;
             }
+            if let Some(ref dir) = download_directory {
+                let mut prefs = FirefoxPreferences::new();
+                prefs.set("browser.download.folderList", 2)?;
+                prefs.set("browser.download.dir", dir.as_str())?;
+                prefs.set("browser.download.useDownloadDir", true)?;
+                prefs.set("browser.download.manager.showWhenStarting", false)?;
+                prefs.set(
+                    "browser.helperApps.neverAsk.saveToDisk",
+                    "text/csv,text/plain,application/csv,application/json,application/pdf,\
+                     application/zip,application/octet-stream,application/vnd.ms-excel,\
+                     application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                )?;
+                caps.set_preferences(prefs)?;
+            }
             WebDriver::new(&localhost, caps)
                 .await
                 .context("Could not launch WebDriver")
@@ -64,8 +99,12 @@ pub async fn new_driver(
             caps.add_chrome_arg("--disable-infobars")?;
             caps.add_chrome_arg("start-maximized")?;
             caps.add_chrome_arg("--disable-extensions")?;
-            let mut prefs = HashMap::new();
-            let _ = prefs.insert("profile.default_content_setting_values.notifications", 1);
+            let mut prefs = json!({ "profile.default_content_setting_values.notifications": 1 });
+            if let Some(ref dir) = download_directory {
+                prefs["download.default_directory"] = json!(dir);
+                prefs["download.prompt_for_download"] = json!(false);
+                prefs["download.directory_upgrade"] = json!(true);
+            }
             caps.add_chrome_option("prefs", prefs)?;
             WebDriver::new(&localhost, caps)
                 .await
@@ -1,6 +1,11 @@
+use camino::Utf8Path;
+use schnauzer_ui::{parser::Parser, scanner::Scanner};
 use serial_test::serial;
 mod common;
-use crate::common::{run_script_against, run_script_against_fails};
+use crate::common::{
+    run_script_against, run_script_against_fails, run_script_for_report, run_script_from_directory,
+    TEST_DOWNLOAD_DIRECTORY,
+};
 
 #[tokio::test]
 #[serial]
@@ -96,6 +101,48 @@ async fn try_again() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn try_again_gives_up_after_its_retries() {
+    let report = run_script_for_report(
+        "set locate-timeout \"1\"\nlocate \"Clicked\"\ncatch-error: try-again \"2\"",
+        "<button>Click Me</button>",
+    )
+    .await;
+    assert!(report.exited_early);
+
+    let failed_locates = report
+        .executed_stmts
+        .iter()
+        .filter(|stmt| stmt.text == "locate \"Clicked\"")
+        .map(|stmt| stmt.retry)
+        .collect::<Vec<_>>();
+    assert_eq!(failed_locates, vec![None, Some(1), Some(2)]);
+
+    let gave_up = report.executed_stmts.last().unwrap();
+    assert_eq!(gave_up.retry, Some(2));
+    assert_eq!(
+        gave_up.error.as_deref(),
+        Some("Still failing after 2 retries")
+    );
+}
+
+#[test]
+fn try_again_takes_retries_and_backoff() {
+    for code in [
+        "catch-error: try-again",
+        "catch-error: try-again \"5\"",
+        "catch-error: try-again \"3\" backoff \"2\" seconds",
+        "catch-error: try-again backoff \"2\" seconds",
+        "catch-error: try-again backoff",
+    ] {
+        let stmts = Parser::new()
+            .parse(Scanner::from_src(code.into()).scan())
+            .unwrap();
+        assert_eq!(stmts[0].to_string(), code);
+    }
+}
+
 #[tokio::test]
 #[serial]
 async fn accept_alert() {
@@ -124,6 +171,53 @@ async fn dismiss_alert() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn read_table_to() {
+    // Reads the table, then locates the paragraph whose id is a cell's text
+    run_script_against(
+        "locate \"Email\" and read-table-to users and locate users[2][\"Account Status\"]",
+        "<table>
+            <thead><tr><th>Email</th><th>Account Status</th></tr></thead>
+            <tbody>
+                <tr><td>alice@x.com</td><td>active</td></tr>
+                <tr><td>bob@x.com</td><td>locked</td></tr>
+            </tbody>
+        </table>
+        <p id=\"locked\">Found</p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_row() {
+    // Clicks the Edit button in Bob's row, which changes that button's text
+    run_script_against(
+        "locate-row where \"Email\" is \"bob@x.com\" and locate \"Edit\" and click and locate \"Editing Bob\"",
+        "<table>
+            <tr><th>Email</th><th>Actions</th></tr>
+            <tr><td>alice@x.com</td><td><button onclick=\"this.textContent = 'Editing Alice'\">Edit</button></td></tr>
+            <tr><td>bob@x.com</td><td><button onclick=\"this.textContent = 'Editing Bob'\">Edit</button></td></tr>
+        </table>",
+    )
+    .await;
+}
+
+#[test]
+fn locate_row_keywords_can_still_be_variable_names() {
+    let stmts = Parser::new()
+        .parse(
+            Scanner::from_src(
+                "save \"Email\" as where\nsave \"bob@x.com\" as is\nlocate-row where where is is"
+                    .into(),
+            )
+            .scan(),
+        )
+        .unwrap();
+    assert_eq!(stmts[2].to_string(), "locate-row where where is is");
+}
+
 #[tokio::test]
 #[serial]
 async fn upload() {
@@ -136,6 +230,72 @@ async fn upload() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn upload_multiple_files() {
+    // The input shows how many files it holds, so locating that text checks both were attached
+    run_script_against(
+        "locate \"myfiles\" and upload \"./tests/assets/test_file_for_upload.txt\" \"./tests/assets/second_file_for_upload.txt\"\nlocate \"2 files attached\"",
+        "<input type=\"file\" id=\"myfiles\" name=\"myfiles\" multiple onchange=\"document.getElementById('count').textContent = this.files.length + ' files attached'\"><p id=\"count\"></p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn upload_resolves_paths_from_the_script_directory() {
+    let report = run_script_from_directory(
+        "locate \"myfile\" and upload \"test_file_for_upload.txt\"\nlocate \"test_file_for_upload.txt attached\"",
+        "<input type=\"file\" id=\"myfile\" name=\"myfile\" onchange=\"document.getElementById('name').textContent = this.files[0].name + ' attached'\"><p id=\"name\"></p>",
+        Utf8Path::new("./tests/assets"),
+    )
+    .await;
+    assert!(!report.exited_early);
+}
+
+#[tokio::test]
+#[serial]
+async fn upload_through_button_for_hidden_input() {
+    run_script_against(
+        "locate \"Upload\" and upload \"./tests/assets/test_file_for_upload.txt\"",
+        "<div class=\"uploader\"><button>Upload</button><input type=\"file\" style=\"display: none;\"></div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn expect_download() {
+    // Downloads a csv, then locates the paragraph whose id is the csv contents
+    run_script_against(
+        "locate \"Export CSV\" and click and expect-download \"*.csv\" and read-download-to contents and locate contents",
+        "<a href=\"data:text/csv,the-contents\" download=\"export.csv\">Export CSV</a><p id=\"the-contents\">Found</p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn expect_download_ignores_files_from_earlier_runs() {
+    // The left over export matches, but nothing gets downloaded, so the command keeps waiting
+    std::fs::create_dir_all(TEST_DOWNLOAD_DIRECTORY).unwrap();
+    std::fs::write(
+        format!("{}/export.csv", TEST_DOWNLOAD_DIRECTORY),
+        "from-an-earlier-run",
+    )
+    .unwrap();
+    let report = run_script_for_report(
+        "timeout \"3\"\nexpect-download \"*.csv\"",
+        "<p>Nothing to download</p>",
+    )
+    .await;
+    assert!(report.exited_early);
+    assert_eq!(
+        report.executed_stmts[2].error.as_deref(),
+        Some("The statement took longer than 3 seconds")
+    );
+}
+
 #[tokio::test]
 #[serial]
 async fn drag_to() {
@@ -179,3 +339,43 @@ async fn drag_to() {
         
         </html>"#).await;
 }
+
+#[tokio::test]
+#[serial]
+async fn drag_to_with_pointer() {
+    // The target only learns about the drop through mouse events,
+    // which the HTML5 simulation never fires.
+    run_script_against(
+        "locate \"Drag Me\" and drag-to \"Drop Here\" and locate \"Dropped\"",
+        r#"<div id="source" style="width: 100px; height: 50px;">Drag Me</div>
+        <div id="target" style="width: 100px; height: 50px; margin-top: 100px;">Drop Here</div>
+        <script>
+            var dragging = false;
+            document.getElementById('source').addEventListener('mousedown', function() { dragging = true; });
+            document.getElementById('target').addEventListener('mouseup', function() {
+                if (dragging) { document.getElementById('target').textContent = 'Dropped'; }
+            });
+        </script>"#,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn drag_by() {
+    run_script_against(
+        "locate \"handle\" and drag-by \"50\" \"0\" and locate \"Moved\"",
+        r#"<div id="handle" style="width: 20px; height: 20px; background: black;"></div>
+        <p id="output">Not Moved</p>
+        <script>
+            var startX = null;
+            document.getElementById('handle').addEventListener('mousedown', function(e) { startX = e.clientX; });
+            document.addEventListener('mouseup', function(e) {
+                if (startX !== null && e.clientX - startX >= 50) {
+                    document.getElementById('output').textContent = 'Moved';
+                }
+            });
+        </script>"#,
+    )
+    .await;
+}
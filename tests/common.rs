@@ -1,4 +1,10 @@
+// Each test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
 use anyhow::Result;
+use camino::Utf8Path;
 use schnauzer_ui::{
     interpreter::Interpreter,
     parser::Parser,
@@ -8,31 +14,85 @@ use schnauzer_ui::{
 };
 use thirtyfour::WebDriver;
 
-const TEST_FILE_NAME: &'static str = "testing_file.html";
+const TEST_FILE_NAME: &str = "testing_file.html";
+pub const TEST_DOWNLOAD_DIRECTORY: &str = "test_downloads";
 
 /// Equivalent to the libraries run function, but produces no test report.
-pub async fn run_test_script(code: String, driver: WebDriver) -> Result<StandardReport> {
+pub async fn run_test_script(
+    code: String,
+    driver: WebDriver,
+    locator_cache: Option<&Utf8Path>,
+    script_directory: Option<&Utf8Path>,
+    script_timeout: Option<Duration>,
+) -> Result<StandardReport> {
     let tokens = Scanner::from_src(code).scan();
     let stmts = Parser::new().parse(tokens)?;
-    Interpreter::new(driver, stmts, false).interpret(true).await
+    let mut interpreter = Interpreter::new(driver, stmts, false);
+    interpreter.set_download_directory(TEST_DOWNLOAD_DIRECTORY.into());
+    if let Some(dir) = script_directory {
+        interpreter.set_script_directory(dir.to_owned());
+    }
+    if let Some(path) = locator_cache {
+        interpreter.set_locator_cache(path)?;
+    }
+    if let Some(timeout) = script_timeout {
+        interpreter.set_script_timeout(timeout);
+    }
+    interpreter.interpret(true).await
 }
 
 /// The purpose of this function is to take in a SchnauzerUI script
 /// and some HTML, and to create a file with the html, run the script
-/// against the file, and return the result
+/// against the file, and return the report.
 /// The script should not include navigating to a url, the test
 /// function will add that to it.
-async fn _run_script_against(script: &str, target_html: &str, should_fail: bool) {
-    // Write the target html to the test file
-    std::fs::write(TEST_FILE_NAME, target_html).expect("Could not write html to file");
+pub async fn run_script_for_report(script: &str, target_html: &str) -> StandardReport {
+    run_script_with(script, target_html, None, None, None).await
+}
+
+/// Same as `run_script_for_report`, but keeps a locator cache (for self-healing) in the given file.
+pub async fn run_script_with_locator_cache(
+    script: &str,
+    target_html: &str,
+    locator_cache: Option<&Utf8Path>,
+) -> StandardReport {
+    run_script_with(script, target_html, locator_cache, None, None).await
+}
+
+/// Same as `run_script_for_report`, but as if the script was saved in the given directory,
+/// so relative paths in it resolve from there.
+pub async fn run_script_from_directory(
+    script: &str,
+    target_html: &str,
+    script_directory: &Utf8Path,
+) -> StandardReport {
+    run_script_with(script, target_html, None, Some(script_directory), None).await
+}
 
+/// Same as `run_script_for_report`, but the script fails with a timeout once it runs
+/// longer than the given time.
+pub async fn run_script_with_script_timeout(
+    script: &str,
+    target_html: &str,
+    script_timeout: Duration,
+) -> StandardReport {
+    run_script_with(script, target_html, None, None, Some(script_timeout)).await
+}
+
+async fn run_script_with(
+    script: &str,
+    target_html: &str,
+    locator_cache: Option<&Utf8Path>,
+    script_directory: Option<&Utf8Path>,
+    script_timeout: Option<Duration>,
+) -> StandardReport {
     // Append the url command to the script
     let mut test_script = format!(
         "url \"file://{}/{}\"",
         std::env::current_dir().unwrap().display(),
         TEST_FILE_NAME
     );
-    test_script.push_str("\n");
+    test_script.push('\n');
     test_script.push_str(script);
 
     // Create a test driver
@@ -40,17 +100,32 @@ async fn _run_script_against(script: &str, target_html: &str, should_fail: bool)
         port: 4444,
         headless: true,
         browser: SupportedBrowser::Firefox,
+        download_directory: Some(TEST_DOWNLOAD_DIRECTORY.into()),
     })
     .await
     .expect("Could not create test driver");
 
-    let result = run_test_script(test_script, driver)
-        .await
-        .expect("Error running script");
+    // Write the target html to the test file
+    std::fs::write(TEST_FILE_NAME, target_html).expect("Could not write html to file");
 
-    assert!(result.exited_early == should_fail);
+    let result = run_test_script(
+        test_script,
+        driver,
+        locator_cache,
+        script_directory,
+        script_timeout,
+    )
+    .await;
 
+    // Clean up before checking the result, so a failing script doesn't leave the page behind
     std::fs::remove_file(TEST_FILE_NAME).expect("Error deleting test file");
+    let _ = std::fs::remove_dir_all(TEST_DOWNLOAD_DIRECTORY);
+    result.expect("Error running script")
+}
+
+async fn _run_script_against(script: &str, target_html: &str, should_fail: bool) {
+    let result = run_script_for_report(script, target_html).await;
+    assert!(result.exited_early == should_fail);
 }
 
 pub async fn run_script_against(script: &str, target_html: &str) {
@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Context, Result};
+use schnauzer_ui::{
+    error::{ErrorKind, ScriptError},
+    parser::Parser,
+    scanner::Scanner,
+};
+use serial_test::serial;
+use thirtyfour::prelude::WebDriverError;
+mod common;
+use crate::common::{run_script_against, run_script_against_fails, run_script_for_report};
+
+#[test]
+fn error_kinds_look_through_context() {
+    let not_found: Result<()> =
+        Err(ScriptError::new(ErrorKind::NotFound, "Could not locate \"Save\"").into());
+    let not_found = not_found
+        .context("Error in the under statement")
+        .unwrap_err();
+    assert_eq!(ErrorKind::of(&not_found), Some(ErrorKind::NotFound));
+    assert_eq!(not_found.to_string(), "Error in the under statement");
+
+    let timeout: Result<(), WebDriverError> = Err(WebDriverError::Timeout("page load".into()));
+    let timeout = timeout.context("Error clicking element").unwrap_err();
+    assert_eq!(ErrorKind::of(&timeout), Some(ErrorKind::Timeout));
+
+    assert_eq!(ErrorKind::of(&anyhow!("Variable is not yet defined")), None);
+}
+
+#[test]
+fn catch_error_parses_error_kinds() {
+    for code in [
+        "catch-error: screenshot",
+        "catch-error \"not-found\": screenshot",
+        "catch-error \"timeout\" \"navigation\": refresh and try-again",
+    ] {
+        let stmts = Parser::new()
+            .parse(Scanner::from_src(code.into()).scan())
+            .unwrap();
+        assert_eq!(stmts[0].to_string(), code);
+    }
+
+    let error = Parser::new()
+        .parse(Scanner::from_src("catch-error \"missing\": screenshot".into()).scan())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Unknown kind of error \"missing\""));
+}
+
+#[tokio::test]
+#[serial]
+async fn catch_error_handles_matching_kinds() {
+    run_script_against(
+        "locate \"Missing\" within \"1\" seconds\ncatch-error \"not-found\": locate \"Present\"",
+        "<p>Present</p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn catch_error_skips_other_kinds() {
+    // The navigation handler is skipped, so the not-found error ends the script
+    run_script_against_fails(
+        "locate \"Missing\" within \"1\" seconds\ncatch-error \"navigation\": locate \"Present\"",
+        "<p>Present</p>",
+    )
+    .await;
+
+    // ...unless a later handler catches it
+    let report = run_script_for_report(
+        "locate \"Missing\" within \"1\" seconds\ncatch-error \"navigation\": screenshot\ncatch-error: locate \"Present\"",
+        "<p>Present</p>",
+    )
+    .await;
+    assert!(!report.exited_early);
+    let navigation_handler = report
+        .executed_stmts
+        .iter()
+        .find(|stmt| stmt.text.starts_with("catch-error \"navigation\""))
+        .unwrap();
+    assert!(navigation_handler.skipped);
+    assert!(navigation_handler.error.is_none());
+}
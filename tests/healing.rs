@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use camino::Utf8Path;
+use schnauzer_ui::healing::{best_match, Fingerprint};
+use serial_test::serial;
+mod common;
+use crate::common::run_script_with_locator_cache;
+
+fn button(id: &str, text: &str, path: &str) -> Fingerprint {
+    Fingerprint {
+        tag: "button".to_owned(),
+        id: id.to_owned(),
+        classes: vec!["btn".to_owned(), "btn-primary".to_owned()],
+        text: text.to_owned(),
+        path: path.to_owned(),
+        attributes: BTreeMap::from([("type".to_owned(), "submit".to_owned())]),
+    }
+}
+
+#[test]
+fn fingerprint_heals_changed_text() {
+    let recorded = button("", "Sign in", "html/body/form/button[2]");
+    let candidates = vec![
+        button("", "Cancel", "html/body/form/button[1]"),
+        button("", "Log in", "html/body/form/button[2]"),
+    ];
+    assert_eq!(best_match(&recorded, &candidates), Some(1));
+    assert_eq!(candidates[1].suggested_locator(), Some("Log in".to_owned()));
+}
+
+#[test]
+fn fingerprint_does_not_heal_to_a_different_element() {
+    let recorded = button("submit", "Sign in", "html/body/form/button");
+    let mut link = button("", "Pricing", "html/body/nav/a");
+    link.tag = "a".to_owned();
+    let other = Fingerprint {
+        classes: vec![],
+        attributes: BTreeMap::new(),
+        ..button("", "Close", "html/body/div/button")
+    };
+    assert_eq!(best_match(&recorded, &[link, other]), None);
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_heals_changed_text() {
+    let cache = Utf8Path::new("test_locators.json");
+    let _ = std::fs::remove_file(cache);
+
+    // The first run records what the button looks like
+    let script = "locate \"Sign in\" within \"1\" seconds and click";
+    let report = run_script_with_locator_cache(
+        script,
+        "<form><input id=\"user\" /><button id=\"sign-in\" class=\"btn\">Sign in</button></form>",
+        Some(cache),
+    )
+    .await;
+    assert!(!report.exited_early);
+
+    // The copy changes, and the second run heals the locator
+    let report = run_script_with_locator_cache(
+        script,
+        "<form><input id=\"user\" /><button id=\"sign-in\" class=\"btn\">Log in</button></form>",
+        Some(cache),
+    )
+    .await;
+    assert!(!report.exited_early);
+    let healed = &report.executed_stmts.last().unwrap().healed;
+    assert_eq!(healed.len(), 1);
+    assert_eq!(healed[0].suggestion.as_deref(), Some("Log in"));
+
+    std::fs::remove_file(cache).expect("Error deleting locator cache");
+}
+
+#[tokio::test]
+#[serial]
+async fn an_unwritable_locator_cache_does_not_fail_the_run() {
+    // The cache's directory doesn't exist, so it can be read (as empty) but not written
+    let cache = Utf8Path::new("missing_directory/test_locators.json");
+    let report = run_script_with_locator_cache(
+        "locate \"Sign in\" and click",
+        "<button>Sign in</button>",
+        Some(cache),
+    )
+    .await;
+    assert!(!report.exited_early);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0]
+        .starts_with("Error writing locator cache missing_directory/test_locators.json"));
+}
@@ -1,6 +1,95 @@
+use std::time::Duration;
+
+use schnauzer_ui::locator::{
+    closest_matches, rank_by_relation, xpath_literal, Candidate, Locators, Rect, Relation,
+};
+use schnauzer_ui::{parser::Parser, scanner::Scanner, settings::format_seconds};
 use serial_test::serial;
 mod common;
-use crate::common::run_script_against;
+use crate::common::{run_script_against, run_script_against_fails, run_script_for_report};
+
+#[test]
+fn failure_timeouts_read_as_seconds() {
+    assert_eq!(format_seconds(Duration::from_secs(1)), "1 second");
+    assert_eq!(format_seconds(Duration::from_millis(2500)), "2.5 seconds");
+    assert_eq!(format_seconds(Duration::from_secs(30)), "30 seconds");
+}
+
+#[test]
+fn xpath_literal_quotes_text() {
+    assert_eq!(xpath_literal("Sign in"), "'Sign in'");
+    assert_eq!(
+        xpath_literal("Don't have an account?"),
+        "\"Don't have an account?\""
+    );
+    assert_eq!(xpath_literal("Say \"hi\""), "'Say \"hi\"'");
+    assert_eq!(
+        xpath_literal("Don't say \"hi\""),
+        "concat('Don', \"'\", 't say \"hi\"')"
+    );
+    assert_eq!(xpath_literal("'"), "\"'\"");
+}
+
+#[test]
+fn closest_matches_suggests_near_misses() {
+    let candidate = |text: &str, kind: &str| Candidate {
+        text: text.to_owned(),
+        kind: kind.to_owned(),
+    };
+    let candidates = vec![
+        candidate("Log in", "button"),
+        candidate("Login help", "link"),
+        candidate("Pricing", "link"),
+        candidate("username", "id"),
+    ];
+
+    let suggestions = closest_matches("Log In", &candidates, 3);
+    assert_eq!(suggestions, vec![&candidates[0], &candidates[1]]);
+
+    let suggestions = closest_matches("Pricng", &candidates, 3);
+    assert_eq!(suggestions, vec![&candidates[2]]);
+
+    assert!(closest_matches("Checkout", &candidates, 3).is_empty());
+}
+
+#[test]
+fn rank_by_relation_filters_and_sorts_by_distance() {
+    let rect = |x: f64, y: f64| Rect {
+        x,
+        y,
+        width: 50.0,
+        height: 20.0,
+    };
+    let anchor = rect(100.0, 100.0);
+    let candidates = vec![
+        rect(300.0, 100.0), // far right
+        rect(160.0, 100.0), // just right
+        rect(100.0, 40.0),  // above
+        rect(100.0, 130.0), // below
+        rect(20.0, 100.0),  // left
+    ];
+
+    assert_eq!(
+        rank_by_relation(Relation::RightOf, &anchor, &candidates),
+        vec![1, 0]
+    );
+    assert_eq!(
+        rank_by_relation(Relation::LeftOf, &anchor, &candidates),
+        vec![4]
+    );
+    assert_eq!(
+        rank_by_relation(Relation::Above, &anchor, &candidates),
+        vec![2]
+    );
+    assert_eq!(
+        rank_by_relation(Relation::Below, &anchor, &candidates),
+        vec![3]
+    );
+    assert_eq!(
+        rank_by_relation(Relation::Near, &anchor, &candidates),
+        vec![3, 1, 2, 4, 0]
+    );
+}
 
 #[tokio::test]
 #[serial]
@@ -101,3 +190,470 @@ async fn locate_by_partial_text_adjacent_to_element() {
         <i class=\"fa fa-plus btn-icon\"></i> Add Provider Data File</a>"
     ).await;
 }
+
+#[tokio::test]
+#[serial]
+async fn locate_with_custom_locator_order() {
+    // With the default order the button's text would match before the other button's id
+    run_script_against(
+        "set locator-order \"id, text\"\nlocate \"target\" and click and locate \"Clicked\"",
+        "<button>target</button><button id=\"target\" onclick=\"this.textContent = 'Clicked'\">Other</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn contains_uses_the_last_match_on_the_page() {
+    run_script_against(
+        "set locator-order \"contains\"\nlocate \"Save\" and click and locate \"Second clicked\"",
+        "<button onclick=\"this.textContent = 'First clicked'\">Save</button><button onclick=\"this.textContent = 'Second clicked'\">Save</button>",
+    )
+    .await;
+}
+
+#[test]
+fn only_documented_prefixes_pick_a_strategy() {
+    let locators = Locators::default();
+    assert_eq!(locators.strategies_for("id:submit"), vec!["id"]);
+    assert_eq!(locators.strategies_for("label:Email"), vec!["label"]);
+
+    // Text with a colon, role names and escaped prefixes use the heuristics
+    let heuristics = locators.names();
+    assert_eq!(locators.strategies_for("Note: required"), heuristics);
+    assert_eq!(locators.strategies_for("tab:Settings"), heuristics);
+    assert_eq!(locators.strategies_for("\\id:submit"), heuristics);
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_with_a_colon() {
+    run_script_against(
+        "locate \"Note: required\" and click and locate \"Clicked\"",
+        "<button onclick=\"this.textContent = 'Clicked'\">Note: required</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_with_id_prefix() {
+    // Without the prefix, the first button's text would match before the second button's id
+    run_script_against(
+        "locate \"id:submit\" and click and locate \"Clicked\"",
+        "<button>submit</button><button id=\"submit\" onclick=\"this.textContent = 'Clicked'\">Other</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_with_css_prefix() {
+    run_script_against(
+        "locate \"css:.btn-primary\" and click and locate \"Clicked\"",
+        "<button class=\"btn\">Cancel</button><button class=\"btn btn-primary\" onclick=\"this.textContent = 'Clicked'\">Save</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_with_testid_prefix() {
+    run_script_against(
+        "locate \"testid:checkout\" and click and locate \"Clicked\"",
+        "<button data-testid=\"checkout\" onclick=\"this.textContent = 'Clicked'\">Buy</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_with_label_prefix() {
+    run_script_against(
+        "locate \"label:Email\" and type \"test@test.com\" and locate \"//input[@id='email']\"",
+        "<label for=\"email\">Email</label><input id=\"email\" type=\"text\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_with_apostrophe() {
+    run_script_against(
+        "locate \"Don't have an account?\" and click and locate \"Clicked\"",
+        "<a onclick=\"this.textContent = 'Clicked'\">Don't have an account?</a>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_placeholder_with_apostrophe() {
+    run_script_against(
+        "locate \"What's your name?\" and type \"Schnauzer\"",
+        "<input type=\"text\" placeholder=\"What's your name?\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_with_both_quotes() {
+    // Reads text with both kinds of quotes into a variable, then locates by it
+    run_script_against(
+        "locate \"quote-source\" and read-to quote\nlocate quote",
+        "<p id=\"quote-source\">Don't say \"hello\"</p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_by_number() {
+    run_script_against(
+        "locate \"Edit\" number \"2\" and click and locate \"Edited 2\"",
+        "<button onclick=\"this.textContent = 'Edited 1'\">Edit</button><button onclick=\"this.textContent = 'Edited 2'\">Edit</button>",
+    )
+    .await;
+}
+
+#[test]
+fn timeout_keywords_can_still_be_variable_names() {
+    let stmts = Parser::new()
+        .parse(
+            Scanner::from_src("save \"5\" as seconds\nlocate within within seconds seconds".into())
+                .scan(),
+        )
+        .unwrap();
+    assert_eq!(stmts[1].to_string(), "locate within within seconds seconds");
+}
+
+#[test]
+fn relation_keywords_can_still_be_variable_names() {
+    for code in [
+        "locate near",
+        "locate above below below",
+        "locate \"Edit\" right-of near",
+    ] {
+        let stmts = Parser::new()
+            .parse(Scanner::from_src(code.into()).scan())
+            .unwrap();
+        assert_eq!(stmts[0].to_string(), code);
+    }
+}
+
+#[test]
+fn ordinal_keywords_can_still_be_variable_names() {
+    for code in [
+        "locate last",
+        "locate number",
+        "locate last last",
+        "locate number number \"2\"",
+    ] {
+        let stmts = Parser::new()
+            .parse(Scanner::from_src(code.into()).scan())
+            .unwrap();
+        assert_eq!(stmts[0].to_string(), code);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_last() {
+    run_script_against(
+        "locate last \"Delete\" and click and locate \"Deleted 3\"",
+        "<button>Delete</button><button>Delete</button><button onclick=\"this.textContent = 'Deleted 3'\">Delete</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_number_out_of_range_fails() {
+    run_script_against_fails(
+        "locate \"Edit\" number \"3\"",
+        "<button>Edit</button><button>Edit</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ambiguous_locator_fails_in_strict_mode() {
+    run_script_against_fails(
+        "set ambiguous-locators \"fail\"\nlocate \"Edit\"",
+        "<button>Edit</button><button>Edit</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ambiguous_locator_failure_has_its_own_kind() {
+    // The assertion-failed handler would end the script, so only the ambiguous one may run
+    run_script_against(
+        "set ambiguous-locators \"fail\"\nlocate \"Edit\"\ncatch-error \"assertion-failed\": locate \"Missing\" within \"1\" seconds\ncatch-error \"ambiguous\": locate \"Present\"",
+        "<button>Edit</button><button>Edit</button><p>Present</p>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ambiguous_locator_with_hidden_match_passes_in_strict_mode() {
+    run_script_against(
+        "set ambiguous-locators \"fail\"\nlocate \"Edit\" and click",
+        "<button>Edit</button><button style=\"display: none\">Edit</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_button_by_role() {
+    // The link has the same text, but isn't a button
+    run_script_against(
+        "locate button \"Submit\" and click and locate \"Clicked\"",
+        "<a href=\"#\">Submit</a><div role=\"button\" onclick=\"this.textContent = 'Clicked'\">Submit</div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_link_by_role() {
+    run_script_against(
+        "locate link \"Pricing\" and click and locate \"Clicked\"",
+        "<button>Pricing</button><a href=\"#\" onclick=\"this.textContent = 'Clicked'\">Pricing</a>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_textbox_by_label() {
+    run_script_against(
+        "locate textbox \"Email\" and type \"test@test.com\"",
+        "<label for=\"email\">Email</label><input id=\"email\" type=\"email\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_checkbox_by_wrapping_label() {
+    run_script_against(
+        "locate checkbox \"Remember me\" and click",
+        "<label><input type=\"checkbox\" /> Remember me</label>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_by_role_and_aria_labelledby() {
+    run_script_against(
+        "locate textbox \"Search terms\" and type \"schnauzer\"",
+        "<span id=\"search-label\">Search terms</span><input type=\"text\" aria-labelledby=\"search-label\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_by_missing_role_fails() {
+    run_script_against_fails("locate button \"Pricing\"", "<a href=\"#\">Pricing</a>").await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_waits_for_element_to_appear() {
+    run_script_against(
+        "locate \"Loaded\" within \"5\" seconds",
+        "<div id=\"status\">Loading</div><script>setTimeout(() => document.getElementById('status').textContent = 'Loaded', 1000)</script>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_gives_up_after_statement_timeout() {
+    run_script_against_fails(
+        "locate \"Loaded\" within \"1\" seconds",
+        "<div id=\"status\">Loading</div><script>setTimeout(() => document.getElementById('status').textContent = 'Loaded', 3000)</script>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_gives_up_after_script_timeout() {
+    run_script_against_fails(
+        "set locate-timeout \"1\"\nset poll-interval \"100\"\nlocate \"Loaded\"",
+        "<div id=\"status\">Loading</div><script>setTimeout(() => document.getElementById('status').textContent = 'Loaded', 3000)</script>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_locate_suggests_near_misses() {
+    let report = run_script_for_report(
+        "locate \"Log In\" within \"1\" seconds",
+        "<button>Log in</button><a href=\"#\">Login help</a><a href=\"#\">Pricing</a>",
+    )
+    .await;
+    let failure = report.executed_stmts.last().unwrap();
+    let error = failure.error.as_ref().unwrap();
+    assert!(error.starts_with("Could not locate \"Log In\" within 1 second."));
+    assert!(error.contains("Tried strategies: placeholder, text"));
+    assert!(error.contains("Did you mean 'Log in' (button) or 'Login help' (link)?"));
+    assert_eq!(failure.screenshots.len(), 1);
+}
+
+/// A web component rendering a button and an input into an open shadow root.
+const SHADOW_FORM: &str = "<login-form id=\"first\"></login-form><login-form id=\"second\"></login-form><script>
+customElements.define('login-form', class extends HTMLElement {
+    connectedCallback() {
+        this.attachShadow({ mode: 'open' }).innerHTML = `
+            <input id=\"user-${this.id}\" placeholder=\"Username\" />
+            <button aria-label=\"Sign in ${this.id}\" onclick=\"this.textContent = 'Clicked ${this.id}'\">Sign in</button>`;
+    }
+});
+</script>";
+
+#[tokio::test]
+#[serial]
+async fn locate_text_in_shadow_root() {
+    run_script_against(
+        "locate \"Sign in\" and click and locate \"Clicked first\"",
+        SHADOW_FORM,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_placeholder_in_shadow_root() {
+    run_script_against("locate \"Username\" and type \"schnauzer\"", SHADOW_FORM).await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_aria_label_and_id_in_shadow_root() {
+    run_script_against(
+        "locate \"Sign in second\" and click and locate \"Clicked second\"\nlocate \"id:user-second\" and type \"schnauzer\"",
+        SHADOW_FORM,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn under_shadow_host() {
+    run_script_against(
+        "under \"second\" locate \"Sign in\" and click\nlocate \"Clicked second\"",
+        SHADOW_FORM,
+    )
+    .await;
+}
+
+/// A grid of people with an edit button in each row, and a field under each heading.
+const PEOPLE_GRID: &str =
+    "<style>div { display: grid; grid-template-columns: 200px 100px; }</style>
+<div>
+    <span>John Smith</span><button onclick=\"this.textContent = 'Editing John'\">Edit</button>
+    <span>Jane Doe</span><button onclick=\"this.textContent = 'Editing Jane'\">Edit</button>
+    <label>First name</label><label>Last name</label>
+    <input id=\"first\" /><input id=\"last\" />
+</div>";
+
+#[tokio::test]
+#[serial]
+async fn locate_right_of() {
+    run_script_against(
+        "locate \"Edit\" right-of \"Jane Doe\" and click and locate \"Editing Jane\"",
+        PEOPLE_GRID,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_near() {
+    run_script_against(
+        "locate \"Edit\" near \"John Smith\" and click and locate \"Editing John\"",
+        PEOPLE_GRID,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_below_left_of_and_above() {
+    run_script_against(
+        "locate \"input\" below \"Last name\" and type \"Doe\"\nlocate \"input\" left-of \"last\" and type \"Jane\"\nlocate \"Edit\" above \"First name\" and click and locate \"Editing Jane\"",
+        PEOPLE_GRID,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_with_nothing_in_direction_fails() {
+    run_script_against_fails(
+        "locate \"Edit\" left-of \"John Smith\" within \"1\" seconds",
+        PEOPLE_GRID,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_split_across_children() {
+    run_script_against(
+        "locate \"Sign in\" and click and locate \"Clicked\"",
+        "<button onclick=\"this.textContent = 'Clicked'\"><b>Sign</b>\n    in</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_with_non_breaking_space() {
+    run_script_against(
+        "locate \"Sign in\" and click and locate \"Clicked\"",
+        "<button onclick=\"this.textContent = 'Clicked'\">Sign&nbsp;in</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_text_ignoring_case() {
+    run_script_against(
+        "locate \"sign in\" and click and locate \"Clicked\"",
+        "<button onclick=\"this.textContent = 'Clicked'\">Sign In</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_non_ascii_text_ignoring_case() {
+    run_script_against(
+        "locate \"état civil\" and click and locate \"Clicked\"",
+        "<button onclick=\"this.textContent = 'Clicked'\">ÉTAT CIVIL</button>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn strict_text_matching_fails_on_case() {
+    run_script_against_fails(
+        "set text-matching \"strict\"\nlocate \"sign in\" within \"1\" seconds",
+        "<button>Sign In</button>",
+    )
+    .await;
+}
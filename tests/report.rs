@@ -0,0 +1,134 @@
+use camino::Utf8PathBuf;
+use schnauzer_ui::test_report::{ExecutedStmt, FailureArtifacts, StandardReport};
+use serial_test::serial;
+mod common;
+use crate::common::run_script_for_report;
+
+#[tokio::test]
+#[serial]
+async fn failures_capture_the_page() {
+    let report = run_script_for_report(
+        "locate \"Missing\" within \"1\" seconds",
+        "<html><head><title>Checkout</title></head><body><p>Almost there</p></body></html>",
+    )
+    .await;
+    let failure = report.executed_stmts.last().unwrap();
+    assert_eq!(failure.screenshots.len(), 1);
+
+    let artifacts = failure.artifacts.as_ref().unwrap();
+    assert!(artifacts
+        .url
+        .as_ref()
+        .unwrap()
+        .ends_with("testing_file.html"));
+    assert_eq!(artifacts.title.as_deref(), Some("Checkout"));
+    assert!(artifacts
+        .page_source
+        .as_ref()
+        .unwrap()
+        .contains("Almost there"));
+
+    // Statements which succeed don't capture anything
+    assert!(report.executed_stmts[0].artifacts.is_none());
+}
+
+#[test]
+fn failed_pages_are_saved_and_linked() {
+    let output_dir = Utf8PathBuf::try_from(std::env::temp_dir())
+        .unwrap()
+        .join("sui_failed_pages_report");
+    let _ = std::fs::remove_dir_all(&output_dir);
+
+    let mut report = StandardReport::new();
+    report.executed_stmts.push(ExecutedStmt {
+        text: "locate \"Missing\"".into(),
+        error: Some("Could not locate \"Missing\"".into()),
+        screenshots: vec![],
+        downloads: vec![],
+        warnings: vec![],
+        notes: vec![],
+        healed: vec![],
+        retry: None,
+        artifacts: Some(FailureArtifacts {
+            url: Some("https://example.com/checkout".into()),
+            title: Some("Checkout".into()),
+            page_source: Some("<p>Almost there</p>".into()),
+        }),
+        cleanup: false,
+        skipped: false,
+    });
+    report
+        .set_testname("checkout".into())
+        .set_output_directory(output_dir.clone())
+        .write_report_default_styling()
+        .unwrap();
+
+    let page = std::fs::read_to_string(output_dir.join("pages/checkout_failure_1.html")).unwrap();
+    assert_eq!(page, "<p>Almost there</p>");
+    let html = std::fs::read_to_string(output_dir.join("checkout.html")).unwrap();
+    assert!(html.contains("href=\"./pages/checkout_failure_1.html\""));
+    assert!(html.contains("href=\"https://example.com/checkout\""));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn downloads_are_saved_and_linked() {
+    let output_dir = Utf8PathBuf::try_from(std::env::temp_dir())
+        .unwrap()
+        .join("sui_downloads_report");
+    let _ = std::fs::remove_dir_all(&output_dir);
+    let download = output_dir.join("browser/monthly report.csv");
+    std::fs::create_dir_all(download.parent().unwrap()).unwrap();
+    std::fs::write(&download, "month,total").unwrap();
+
+    let mut report = StandardReport::new();
+    report.executed_stmts.push(ExecutedStmt {
+        text: "expect-download \"monthly report.csv\"".into(),
+        error: None,
+        screenshots: vec![],
+        downloads: vec![download],
+        warnings: vec![],
+        notes: vec![],
+        healed: vec![],
+        retry: None,
+        artifacts: None,
+        cleanup: false,
+        skipped: false,
+    });
+    report
+        .set_testname("downloads".into())
+        .set_output_directory(output_dir.clone())
+        .write_report_default_styling()
+        .unwrap();
+
+    let saved = std::fs::read_to_string(output_dir.join("downloads/monthly report.csv")).unwrap();
+    assert_eq!(saved, "month,total");
+    let html = std::fs::read_to_string(output_dir.join("downloads.html")).unwrap();
+    assert!(html.contains("href=\"./downloads/monthly report.csv\""));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn run_warnings_are_shown_at_the_top() {
+    let output_dir = Utf8PathBuf::try_from(std::env::temp_dir())
+        .unwrap()
+        .join("sui_run_warnings_report");
+    let _ = std::fs::remove_dir_all(&output_dir);
+
+    let mut report = StandardReport::new();
+    report
+        .warnings
+        .push("Error writing locator cache locators.json".into());
+    report
+        .set_testname("warnings".into())
+        .set_output_directory(output_dir.clone())
+        .write_report_default_styling()
+        .unwrap();
+
+    let html = std::fs::read_to_string(output_dir.join("warnings.html")).unwrap();
+    assert!(html.contains("Warning: Error writing locator cache locators.json"));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
@@ -1,6 +1,6 @@
 use serial_test::serial;
 mod common;
-use crate::common::run_script_against;
+use crate::common::{run_script_against, run_script_for_report};
 
 #[tokio::test]
 #[serial]
@@ -87,3 +87,78 @@ async fn locate_text_input_by_nested_label() {
     )
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn locate_input_by_aria_labelledby() {
+    run_script_against(
+        "locate \"Email address\" and type \"test@test.com\" and locate \"//input[@id='email']\"",
+        "<h3 id=\"email-heading\">Email address</h3><p>Some text</p><input id=\"email\" aria-labelledby=\"email-heading\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_input_by_aria_describedby() {
+    run_script_against(
+        "locate \"Your phone number\" and type \"555-5555\"",
+        "<small id=\"phone-help\">Your phone number</small><input aria-describedby=\"phone-help\" />",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_input_by_div_label() {
+    run_script_against(
+        "locate \"First name\" and type \"Jane\"",
+        "<div class=\"field\"><div class=\"field-label\">First name</div><div class=\"field-control\"><input /></div></div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_contenteditable_by_p_label() {
+    run_script_against(
+        "locate \"Comment\" and type \"Looks good\"",
+        "<p>Comment</p><div contenteditable=\"true\" style=\"min-height: 20px\"></div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn click_div_button_next_to_input() {
+    // A div is only swapped for a nearby control when typing or selecting
+    run_script_against(
+        "locate \"Save\" and click and locate \"Saved\"",
+        "<form><div role=\"button\" onclick=\"this.textContent = 'Saved'\">Save</div><input id=\"name\" /></form>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn locate_role_textbox_by_label() {
+    run_script_against(
+        "locate \"Description\" and type \"A schnauzer\"",
+        "<span>Description</span><div role=\"textbox\" contenteditable=\"true\" style=\"min-height: 20px\"></div>",
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn swap_is_noted_in_report() {
+    let report = run_script_for_report(
+        "locate \"test input element\" and type \"Some Text\"",
+        "<label for=\"test-input\">test input element</label><input id=\"test-input\" type=\"text\" />",
+    )
+    .await;
+    assert!(!report.exited_early);
+    let notes = &report.executed_stmts.last().unwrap().notes;
+    assert!(notes
+        .contains(&"Swapped the located label for input#test-input (for attribute)".to_owned()));
+}
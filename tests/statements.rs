@@ -1,6 +1,16 @@
+use std::time::Duration;
+
+use schnauzer_ui::{
+    parser::{Parser, Stmt},
+    scanner::Scanner,
+    settings::Settings,
+};
 use serial_test::serial;
 mod common;
-use crate::common::run_script_against;
+use crate::common::{
+    run_script_against, run_script_against_fails, run_script_for_report,
+    run_script_with_script_timeout,
+};
 
 #[tokio::test]
 #[serial]
@@ -51,6 +61,51 @@ async fn under() {
     ).await;
 }
 
+/// The navigation example from the docs: the link is two levels up from the heading.
+const NAVIGATION: &str = "<h1>Desired Text</h1>
+<div class='container'>
+  <div class='nav-title'><h3>Navigation</h3></div>
+  <div class='nav-body'><a href='#'>Desired Text</a></div>
+</div>";
+
+#[tokio::test]
+#[serial]
+async fn under_notes_how_far_up_it_searched() {
+    let report = run_script_for_report(
+        "under \"Navigation\" locate \"Desired Text\" and click",
+        NAVIGATION,
+    )
+    .await;
+    assert!(!report.exited_early);
+    assert_eq!(
+        report.executed_stmts[1].notes,
+        vec!["Found \"Desired Text\" 2 levels up from the under element".to_owned()]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn under_radius_bounds_the_search() {
+    let report = run_script_for_report(
+        "set under-radius \"1\"\nunder \"Navigation\" locate \"Desired Text\" within \"1\" seconds",
+        NAVIGATION,
+    )
+    .await;
+    assert!(report.exited_early);
+    let error = report.executed_stmts[2].error.as_ref().unwrap();
+    assert!(error.contains("within 1 level of the under element"));
+}
+
+#[tokio::test]
+#[serial]
+async fn strict_under_never_leaves_the_element() {
+    run_script_against_fails(
+        "set under-mode \"strict\"\nunder \"nav-title\" locate \"Desired Text\" within \"1\" seconds",
+        NAVIGATION,
+    )
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn under_active_element() {
@@ -59,3 +114,287 @@ async fn under_active_element() {
         "<p id='some-elm'>No type here</p><div id='haystack'><input class=\"some-elm\" type=\"text\" /><button>Click me</button></div>"
     ).await;
 }
+
+/// Two forms with the same fields, like a page with an edit dialog open over it.
+const EDIT_USER_DIALOG: &str = "<form><label for='page-name'>First name</label><input id='page-name' /><button type='button' onclick='this.innerText = \"Saved page\"'>Save</button></form>
+<div role='dialog' aria-label='Edit user'>
+  <label for='dialog-name'>First name</label><input id='dialog-name' />
+  <section aria-label='Address'><label for='street'>Street</label><input id='street' /><button type='button' onclick='this.innerText = \"Saved address\"'>Save</button></section>
+  <button type='button' onclick='this.innerText = \"Saved user\"'>Save</button>
+</div>";
+
+#[tokio::test]
+#[serial]
+async fn within_keeps_the_scope_until_end() {
+    run_script_against(
+        "within \"Edit user\"\nlocate \"First name\" and type \"Ada\"\nlocate last \"Save\" and click\nend\nlocate \"Saved user\"\nlocate \"page-name\" and type \"Grace\"",
+        EDIT_USER_DIALOG,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn try_again_reopens_a_within_block_once() {
+    // The retry opens the block again rather than nesting it in itself, so the end closes it
+    run_script_against(
+        "within \"Edit user\"\nlocate \"Saved user\" within \"1\" seconds\ncatch-error: locate last \"Save\" and click and try-again\nend\nlocate \"page-name\" and type \"Grace\"",
+        EDIT_USER_DIALOG,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn within_blocks_nest() {
+    run_script_against(
+        "within \"Edit user\"\nwithin \"Address\"\nlocate \"Save\" and click\nend\nlocate \"Saved address\"\nend",
+        EDIT_USER_DIALOG,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn within_does_not_find_elements_outside_the_scope() {
+    run_script_against_fails(
+        "within \"Address\"\nlocate \"First name\" within \"1\" seconds\nend",
+        EDIT_USER_DIALOG,
+    )
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn within_failures_name_the_scope() {
+    let report = run_script_for_report(
+        "within \"Edit user\"\nwithin \"Address\"\nlocate \"Zip code\" within \"1\" seconds\nend\nend",
+        EDIT_USER_DIALOG,
+    )
+    .await;
+    let error = report.executed_stmts[3].error.as_ref().unwrap();
+    assert!(error.starts_with(
+        "Could not locate \"Zip code\" inside \"Edit user\" > \"Address\" within 1 second."
+    ));
+}
+
+#[test]
+fn within_blocks_must_be_closed() {
+    let unclosed = Scanner::from_src("within \"Edit user\"\nlocate \"Save\"".into()).scan();
+    let error = Parser::new().parse(unclosed).unwrap_err().to_string();
+    assert!(error.contains("never closed"));
+
+    let extra_end = Scanner::from_src("locate \"Save\"\nend".into()).scan();
+    let error = Parser::new().parse(extra_end).unwrap_err().to_string();
+    assert!(error.contains("without a within block"));
+
+    let unclosed = Scanner::from_src("within \"Edit user\"".into()).scan();
+    assert!(Parser::new().allow_open_blocks().parse(unclosed).is_ok());
+}
+
+#[test]
+fn block_keywords_can_still_be_variable_names() {
+    let stmts = Parser::new()
+        .parse(
+            Scanner::from_src(
+                "save \"Edit user\" as within\nsave \"Save\" as end\nwithin within\nlocate end and click\nend"
+                    .into(),
+            )
+            .scan(),
+        )
+        .unwrap();
+    assert_eq!(stmts[2].to_string(), "within within");
+    assert_eq!(stmts[4].to_string(), "end");
+}
+
+#[test]
+fn within_starts_a_block_and_ends_a_locator_with_a_timeout() {
+    let stmts = Parser::new()
+        .parse(
+            Scanner::from_src(
+                "within \"Edit user\" within \"2\" seconds\nlocate \"Save\" within \"1\" seconds\nend"
+                    .into(),
+            )
+            .scan(),
+        )
+        .unwrap();
+    assert!(matches!(stmts[0], Stmt::Within(_)));
+    assert_eq!(
+        stmts[0].to_string(),
+        "within \"Edit user\" within \"2\" seconds"
+    );
+    assert_eq!(stmts[1].to_string(), "locate \"Save\" within \"1\" seconds");
+}
+
+#[tokio::test]
+#[serial]
+async fn soft_failures_are_recorded_and_the_script_keeps_going() {
+    let report = run_script_for_report(
+        "soft locate \"Welcome back!\" within \"1\" seconds\nlocate \"some-elm\" and type \"Some Text\"",
+        "<input id=\"some-elm\" type=\"text\" />",
+    )
+    .await;
+    assert!(!report.exited_early);
+    assert!(report.executed_stmts[1].error.is_some());
+    assert!(report.executed_stmts[2].error.is_none());
+    assert_eq!(report.failures().len(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn a_failed_soft_under_does_not_scope_the_next_statement() {
+    let report = run_script_for_report(
+        "set under-mode \"strict\"\nsoft under \"nav\" locate \"Missing\" within \"1\" seconds and click\nlocate \"Save\" within \"1\" seconds and click",
+        "<div id='nav'><a href='#'>Home</a></div><button type='button'>Save</button>",
+    )
+    .await;
+    assert!(!report.exited_early);
+    assert_eq!(report.failures().len(), 1);
+}
+
+#[test]
+fn soft_only_applies_to_statements_which_can_fail() {
+    let stmts = Parser::new()
+        .parse(Scanner::from_src("soft under \"Navigation\" locate \"Home\"".into()).scan())
+        .unwrap();
+    assert_eq!(
+        stmts[0].to_string(),
+        "soft under \"Navigation\" locate \"Home\""
+    );
+
+    let error = Parser::new()
+        .parse(Scanner::from_src("soft catch-error: screenshot".into()).scan())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Only commands, if and under statements can be soft"));
+}
+
+#[test]
+fn soft_can_still_be_a_variable_name() {
+    let stmts = Parser::new()
+        .parse(Scanner::from_src("save \"Welcome\" as soft\nsoft locate soft".into()).scan())
+        .unwrap();
+    assert_eq!(stmts[1].to_string(), "soft locate soft");
+}
+
+#[tokio::test]
+#[serial]
+async fn cleanup_runs_after_a_failure_without_masking_it() {
+    let report = run_script_for_report(
+        "locate \"missing-elm\" within \"1\" seconds\nlocate \"some-elm\" and type \"Skipped\"\ncleanup:\nlocate \"other-missing-elm\" within \"1\" seconds\nlocate \"some-elm\" and type \"Cleaned up\"",
+        "<input id=\"some-elm\" type=\"text\" />",
+    )
+    .await;
+    assert!(report.exited_early);
+
+    // The url, the failed locate and the skipped type, then both cleanup statements
+    assert_eq!(report.executed_stmts.len(), 5);
+    assert!(!report.executed_stmts[1].cleanup);
+    assert!(report.executed_stmts[2].skipped && !report.executed_stmts[2].cleanup);
+    assert!(report.executed_stmts[3].cleanup && report.executed_stmts[3].error.is_some());
+    assert!(report.executed_stmts[4].cleanup && report.executed_stmts[4].error.is_none());
+}
+
+#[test]
+fn a_script_has_one_cleanup_section_outside_within_blocks() {
+    let stmts = Parser::new()
+        .parse(Scanner::from_src("locate \"Save\" and click\nfinally:\nrefresh".into()).scan())
+        .unwrap();
+    assert_eq!(stmts[1].to_string(), "cleanup:");
+
+    let error = Parser::new()
+        .parse(Scanner::from_src("cleanup:\nrefresh\ncleanup:\nrefresh".into()).scan())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("A script can only have one cleanup section"));
+
+    let error = Parser::new()
+        .parse(Scanner::from_src("within \"Dialog\"\ncleanup:\nend".into()).scan())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("The cleanup section can't start in the middle of a within block"));
+}
+
+#[tokio::test]
+#[serial]
+async fn statements_fail_with_a_timeout_after_the_statement_timeout() {
+    let report = run_script_for_report(
+        "timeout \"1\"\nchill \"5\"\nlocate \"some-elm\" and type \"Skipped\"\ncleanup:\nlocate \"some-elm\" and type \"Cleaned up\"",
+        "<input id=\"some-elm\" type=\"text\" />",
+    )
+    .await;
+    assert!(report.exited_early);
+    assert_eq!(
+        report.executed_stmts[2].error.as_deref(),
+        Some("The statement took longer than 1 second")
+    );
+    assert!(report.executed_stmts[3].skipped);
+    assert!(report.executed_stmts[4].cleanup && report.executed_stmts[4].error.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn a_timeout_while_relocating_a_within_block_keeps_the_block() {
+    // The dialog is re-rendered too late, so the soft locate times out while locating it again
+    let report = run_script_for_report(
+        "within \"Edit user\"\nlocate \"Reopen later\" and click\ntimeout \"1\"\nsoft locate \"First name\"\ntimeout \"unlimited\"\nend\nlocate \"page-name\" and type \"Grace\"",
+        "<input id='page-name' />
+<div role='dialog' aria-label='Edit user'>
+  <label for='dialog-name'>First name</label><input id='dialog-name' />
+  <button type='button' onclick='const dialog = this.parentElement; dialog.remove(); setTimeout(() => document.body.append(dialog), 5000)'>Reopen later</button>
+</div>",
+    )
+    .await;
+    assert!(!report.exited_early);
+    assert_eq!(
+        report.executed_stmts[4].error.as_deref(),
+        Some("The statement took longer than 1 second")
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn the_cleanup_section_has_a_deadline_of_its_own() {
+    let report = run_script_with_script_timeout(
+        "chill \"5\"\ncleanup:\nlocate \"some-elm\" and type \"Cleaned up\"\nchill \"5\"\nlocate \"some-elm\" and type \"Never runs\"",
+        "<input id=\"some-elm\" type=\"text\" />",
+        Duration::from_secs(2),
+    )
+    .await;
+    assert!(report.exited_early);
+    assert_eq!(
+        report.executed_stmts[1].error.as_deref(),
+        Some("The script took longer than 2 seconds")
+    );
+
+    // The cleanup section gets its own 2 seconds, which run out during its chill
+    assert!(report.executed_stmts[2].cleanup && report.executed_stmts[2].error.is_none());
+    assert_eq!(
+        report.executed_stmts[3].error.as_deref(),
+        Some("The cleanup section took longer than 2 seconds")
+    );
+    assert_eq!(report.executed_stmts.len(), 4);
+}
+
+#[test]
+fn timeout_sets_the_statement_timeout() {
+    let stmts = Parser::new()
+        .parse(Scanner::from_src("timeout \"30\"".into()).scan())
+        .unwrap();
+    assert_eq!(stmts[0].to_string(), "timeout \"30\"");
+
+    // Anywhere else, timeout is an ordinary variable name
+    let stmts = Parser::new()
+        .parse(Scanner::from_src("save \"30\" as timeout\ntimeout timeout".into()).scan())
+        .unwrap();
+    assert_eq!(stmts[1].to_string(), "timeout timeout");
+
+    let mut settings = Settings::default();
+    settings.set("statement-timeout", "2.5").unwrap();
+    assert_eq!(
+        settings.statement_timeout,
+        Some(Duration::from_millis(2500))
+    );
+    settings.set("statement-timeout", "unlimited").unwrap();
+    assert_eq!(settings.statement_timeout, None);
+}